@@ -0,0 +1,146 @@
+//! This module contains an async wrapper for pipes, built on tokio's `AsyncFd`.
+
+use crate::{Pipe, PipeBuilder, OnCleanup, Result};
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::os::fd::OwnedFd;
+use std::os::unix::io::{AsRawFd, RawFd};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::io::unix::AsyncFd;
+use nix::unistd;
+
+/// A pipe which implements `AsyncRead` and `AsyncWrite`. The underlying handle
+/// is opened with `O_NONBLOCK` and registered with the tokio reactor, so reads
+/// and writes never block a runtime worker.
+#[derive(Debug)]
+pub struct AsyncPipe
+{
+    inner: AsyncFd<PipeFd>
+}
+
+/// Exposes one handle of a pipe to `AsyncFd`. The handle is a duplicate, so
+/// it stays open even if the pipe is a clone, and pipes sharing a handle can
+/// each be registered with the reactor.
+#[derive(Debug)]
+struct PipeFd
+{
+    pipe: Pipe,
+    handle: OwnedFd
+}
+
+impl AsRawFd for PipeFd
+{
+    fn as_raw_fd(&self) -> RawFd
+    {
+        self.handle.as_raw_fd()
+    }
+}
+
+impl AsyncPipe
+{
+    /// Open or create a pipe. See `Pipe::open`. Must be called from within a 
+    /// tokio runtime with IO enabled.
+    pub fn open(path: &Path, on_cleanup: OnCleanup) -> Result<Self>
+    {
+        PipeBuilder::new()
+            .path(path)
+            .on_cleanup(on_cleanup)
+            .open_async()
+    }
+
+    /// Open or create a pipe with the given name. See `Pipe::with_name`. Must 
+    /// be called from within a tokio runtime with IO enabled.
+    pub fn with_name(name: &str) -> Result<Self>
+    {
+        PipeBuilder::new().name(name).open_async()
+    }
+
+    /// Create a pipe with a randomly generated name in a tempory directory.
+    /// Must be called from within a tokio runtime with IO enabled.
+    #[cfg(feature="rand")]
+    pub fn create() -> Result<Self>
+    {
+        PipeBuilder::new().open_async()
+    }
+
+    /// Registers an already opened pipe with the tokio reactor. The pipe must
+    /// have been opened in non-blocking mode.
+    pub(crate) fn from_pipe(pipe: Pipe) -> Result<Self>
+    {
+        let handle = pipe.first_handle()?;
+        AsyncPipe::from_handle(pipe, &handle)
+    }
+
+    /// Registers one handle of a pipe with the tokio reactor, which is used
+    /// for both reading and writing. The handle must be non-blocking.
+    pub(crate) fn from_handle(pipe: Pipe, handle: &OwnedFd) -> Result<Self>
+    {
+        let handle = handle.try_clone()?;
+        Ok(AsyncPipe { inner: AsyncFd::new(PipeFd { pipe, handle })? })
+    }
+
+    /// Return the path to this named pipe
+    pub fn path(&self) -> &Path
+    {
+        self.inner.get_ref().pipe.path()
+    }
+
+    /// Gets the name of this pipe
+    pub fn name(&self) -> Option<&std::ffi::OsStr>
+    {
+        self.path().file_name()
+    }
+}
+
+impl AsyncRead for AsyncPipe
+{
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>>
+    {
+        loop
+        {
+            let mut guard = ready!(self.inner.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            match guard.try_io(|inner| unistd::read(inner.as_raw_fd(), unfilled).map_err(io::Error::from))
+            {
+                Ok(Ok(len)) => 
+                {
+                    buf.advance(len);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(err)) => return Poll::Ready(Err(err)),
+                // Readiness was cleared, so wait again
+                Err(_) => continue
+            }
+        }
+    }
+}
+
+impl AsyncWrite for AsyncPipe
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>
+    {
+        loop
+        {
+            let mut guard = ready!(self.inner.poll_write_ready(cx))?;
+            match guard.try_io(|inner| unistd::write(inner.as_raw_fd(), buf).map_err(io::Error::from))
+            {
+                Ok(result) => return Poll::Ready(result),
+                // Readiness was cleared, so wait again
+                Err(_) => continue
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>>
+    {
+        // Writes go straight to the kernel, so there's nothing to flush
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>>
+    {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,201 @@
+//! This module contains functions which connect a pipe to a channel. The
+//! `channels` feature provides versions using std threads and channels, and
+//! the `tokio_channels` feature provides versions using tokio tasks and
+//! channels. Both features can be enabled at the same time.
+
+use crate::Pipe;
+#[cfg(any(feature="channels", windows))]
+use std::io::{Read, Write};
+
+impl Pipe
+{
+    /// Creates a receiver which all output from this pipe is directed into. A
+    /// thread is spawned to read from the pipe, which sends each chunk of data
+    /// read as it arrives. If a read fails, the error is sent and the thread
+    /// exits. The thread also exits when the pipe reaches EOF or when the
+    /// receiver is dropped. Note that the thread blocks, and may attempt to
+    /// read from the pipe one time after the receiver is dropped.
+    #[cfg(feature="channels")]
+    pub fn receiver_blocking(mut self) -> (std::sync::mpsc::Receiver<std::io::Result<Vec<u8>>>, std::thread::JoinHandle<()>)
+    {
+        let (tx, rx) = std::sync::mpsc::channel();
+        (rx, std::thread::spawn(move || read_chunks(&mut self, |chunk| tx.send(chunk).is_ok())))
+    }
+
+    /// Creates a sender which outputs all input into this pipe. A thread is
+    /// spawned to write each chunk sent into the pipe. The thread exits when
+    /// the sender is dropped, or when a write fails, in which case the error
+    /// is returned from the thread.
+    #[cfg(feature="channels")]
+    pub fn sender_blocking(mut self) -> (std::sync::mpsc::Sender<Vec<u8>>, std::thread::JoinHandle<std::io::Result<()>>)
+    {
+        let (tx, rx) = std::sync::mpsc::channel::<Vec<u8>>();
+        (tx, std::thread::spawn(move ||
+        {
+            while let Ok(chunk) = rx.recv()
+            {
+                self.write_all(&chunk)?;
+            }
+            Ok(())
+        }))
+    }
+
+    /// Creates a receiver which all output from this pipe is directed into. A
+    /// task is spawned to read from the pipe, which sends each chunk of data
+    /// read as it arrives. If a read fails, the error is sent and the task
+    /// exits. The task also exits when the pipe reaches EOF or as soon as the
+    /// receiver is dropped. The pipe is read through an `AsyncPipe`, so the
+    /// task never blocks a runtime worker and can be dropped along with the
+    /// runtime. Must be called from within a tokio runtime with IO enabled.
+    #[cfg(all(unix, feature="tokio_channels"))]
+    pub fn receiver_async(self) -> (tokio::sync::mpsc::UnboundedReceiver<std::io::Result<Vec<u8>>>, tokio::task::JoinHandle<()>)
+    {
+        use tokio::io::AsyncReadExt;
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (rx, tokio::spawn(async move
+        {
+            let mut pipe = match self.into_async_reader()
+            {
+                Ok(pipe) => pipe,
+                Err(e) =>
+                {
+                    let _ = tx.send(Err(e.into()));
+                    return;
+                }
+            };
+            let mut buf = [0; 4096];
+            loop
+            {
+                let result = tokio::select!
+                {
+                    result = pipe.read(&mut buf) => result,
+                    _ = tx.closed() => break
+                };
+                match result
+                {
+                    Ok(0) => break,
+                    Ok(len) =>
+                    {
+                        if tx.send(Ok(buf[..len].to_vec())).is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+                    Err(e) =>
+                    {
+                        let _ = tx.send(Err(e));
+                        break;
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Creates a sender which outputs all input into this pipe. A task is
+    /// spawned to write each chunk sent into the pipe. The task exits when
+    /// the sender is dropped, or when a write fails, in which case the error
+    /// is returned from the task. The pipe is written through an `AsyncPipe`,
+    /// so the task never blocks a runtime worker and can be dropped along 
+    /// with the runtime. Must be called from within a tokio runtime with IO
+    /// enabled. For a strict pipe, the task waits for a reader to open the 
+    /// pipe first, which also needs the runtime's timer.
+    #[cfg(all(unix, feature="tokio_channels"))]
+    pub fn sender_async(mut self) -> (tokio::sync::mpsc::UnboundedSender<Vec<u8>>, tokio::task::JoinHandle<std::io::Result<()>>)
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Vec<u8>>();
+        (tx, tokio::spawn(async move
+        {
+            let handle = loop
+            {
+                if let Some(handle) = self.try_write_handle()?
+                {
+                    break handle;
+                }
+                tokio::time::sleep(crate::pipe_unix::RETRY_INTERVAL).await;
+            };
+            self.set_nonblocking(true)?;
+            let mut pipe = crate::AsyncPipe::from_handle(self, &handle)?;
+            while let Some(chunk) = rx.recv().await
+            {
+                pipe.write_all(&chunk).await?;
+            }
+            Ok(())
+        }))
+    }
+
+    /// Opens this pipe's read handle without blocking and registers it with 
+    /// the tokio reactor
+    #[cfg(all(unix, feature="tokio_channels"))]
+    fn into_async_reader(mut self) -> crate::Result<crate::AsyncPipe>
+    {
+        let handle = self.read_handle()?;
+        self.set_nonblocking(true)?;
+        crate::AsyncPipe::from_handle(self, &handle)
+    }
+
+    /// Creates a receiver which all output from this pipe is directed into. A
+    /// blocking task is spawned to read from the pipe, which sends each chunk
+    /// of data read as it arrives. If a read fails, the error is sent and the
+    /// task exits. The task also exits when the pipe reaches EOF or when the
+    /// receiver is dropped. Note that the task blocks, and may attempt to read
+    /// from the pipe one time after the receiver is dropped. Must be called
+    /// from within a tokio runtime.
+    #[cfg(all(windows, feature="tokio_channels"))]
+    pub fn receiver_async(mut self) -> (tokio::sync::mpsc::UnboundedReceiver<std::io::Result<Vec<u8>>>, tokio::task::JoinHandle<()>)
+    {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (rx, tokio::task::spawn_blocking(move || read_chunks(&mut self, |chunk| tx.send(chunk).is_ok())))
+    }
+
+    /// Creates a sender which outputs all input into this pipe. A blocking
+    /// task is spawned to write each chunk sent into the pipe. The task exits
+    /// when the sender is dropped, or when a write fails, in which case the
+    /// error is returned from the task. Must be called from within a tokio
+    /// runtime.
+    #[cfg(all(windows, feature="tokio_channels"))]
+    pub fn sender_async(mut self) -> (tokio::sync::mpsc::UnboundedSender<Vec<u8>>, tokio::task::JoinHandle<std::io::Result<()>>)
+    {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Vec<u8>>();
+        (tx, tokio::task::spawn_blocking(move ||
+        {
+            while let Some(chunk) = rx.blocking_recv()
+            {
+                self.write_all(&chunk)?;
+            }
+            Ok(())
+        }))
+    }
+}
+
+/// Reads chunks from a pipe until EOF or an error, passing each one to `send`.
+/// Stops early if `send` returns false.
+#[cfg(any(feature="channels", windows))]
+fn read_chunks<F>(pipe: &mut Pipe, send: F)
+    where F: Fn(std::io::Result<Vec<u8>>) -> bool
+{
+    let mut buf = [0; 4096];
+    loop
+    {
+        match pipe.read(&mut buf)
+        {
+            Ok(0) => break,
+            Ok(len) =>
+            {
+                if !send(Ok(buf[..len].to_vec()))
+                {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+            Err(e) =>
+            {
+                send(Err(e));
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! This module contains `tokio_util` codecs for the framing formats used by
+//! this crate, so an `AsyncPipe` can be wrapped in `tokio_util::codec::Framed`
+//! to get a `Stream` and `Sink` of messages.
+
+use crate::{Error, Result, DEFAULT_MAX_MESSAGE_SIZE};
+use crate::message::HEADER_SIZE;
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A codec for length-prefixed messages, using the same framing as
+/// `MessagePipe`. Each message is a 4-byte big-endian length followed by the
+/// message itself.
+///
+/// ```no_run
+/// # async fn example() -> ipipe::Result<()> {
+/// use futures::{SinkExt, StreamExt};
+/// use ipipe::{AsyncPipe, MessageCodec};
+/// use tokio_util::codec::Framed;
+/// let mut framed = Framed::new(AsyncPipe::with_name("my_pipe")?, MessageCodec::new());
+/// framed.send(b"Hello".to_vec()).await?;
+/// let message: Vec<u8> = framed.next().await.unwrap()?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MessageCodec
+{
+    max_size: usize
+}
+
+impl Default for MessageCodec
+{
+    fn default() -> Self
+    {
+        Self::with_max_size(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+}
+
+impl MessageCodec
+{
+    /// Creates a codec with a maximum message size of
+    /// `DEFAULT_MAX_MESSAGE_SIZE`
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Creates a codec with the given maximum message size. Encoding or
+    /// decoding a message larger than this fails with
+    /// `Error::MessageTooLarge`.
+    pub fn with_max_size(max_size: usize) -> Self
+    {
+        MessageCodec { max_size: max_size.min(u32::MAX as usize) }
+    }
+
+    /// Returns the maximum message size
+    pub fn max_size(&self) -> usize
+    {
+        self.max_size
+    }
+}
+
+impl Decoder for MessageCodec
+{
+    type Item = Vec<u8>;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Vec<u8>>>
+    {
+        if src.len() < HEADER_SIZE
+        {
+            return Ok(None);
+        }
+        let mut header = [0; HEADER_SIZE];
+        header.copy_from_slice(&src[..HEADER_SIZE]);
+        let size = u32::from_be_bytes(header) as usize;
+        if size > self.max_size
+        {
+            return Err(Error::MessageTooLarge(size, self.max_size));
+        }
+        if src.len() < HEADER_SIZE + size
+        {
+            src.reserve(HEADER_SIZE + size - src.len());
+            return Ok(None);
+        }
+        src.advance(HEADER_SIZE);
+        Ok(Some(src.split_to(size).to_vec()))
+    }
+
+    /// Fails with `Error::TruncatedMessage` if the pipe reaches EOF partway
+    /// through a message
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Vec<u8>>>
+    {
+        match self.decode(src)?
+        {
+            Some(message) => Ok(Some(message)),
+            None if src.is_empty() => Ok(None),
+            None if src.len() < HEADER_SIZE => Err(Error::TruncatedMessage(src.len(), HEADER_SIZE)),
+            None =>
+            {
+                let size = u32::from_be_bytes([src[0], src[1], src[2], src[3]]) as usize;
+                Err(Error::TruncatedMessage(src.len(), HEADER_SIZE + size))
+            }
+        }
+    }
+}
+
+impl Encoder<&[u8]> for MessageCodec
+{
+    type Error = Error;
+
+    fn encode(&mut self, message: &[u8], dst: &mut BytesMut) -> Result<()>
+    {
+        if message.len() > self.max_size
+        {
+            return Err(Error::MessageTooLarge(message.len(), self.max_size));
+        }
+        dst.reserve(HEADER_SIZE + message.len());
+        dst.put_u32(message.len() as u32);
+        dst.extend_from_slice(message);
+        Ok(())
+    }
+}
+
+impl Encoder<Vec<u8>> for MessageCodec
+{
+    type Error = Error;
+
+    fn encode(&mut self, message: Vec<u8>, dst: &mut BytesMut) -> Result<()>
+    {
+        self.encode(message.as_slice(), dst)
+    }
+}
+
+/// A codec for lines of UTF-8 text, using the same framing as `LinesPipe`.
+/// Lines are separated by `\n`, and a trailing `\r` is removed from decoded
+/// lines.
+#[derive(Debug, Clone)]
+pub struct LineCodec
+{
+    max_size: usize,
+    /// How far into the buffer has already been searched for a newline
+    searched: usize
+}
+
+impl Default for LineCodec
+{
+    fn default() -> Self
+    {
+        Self::with_max_size(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+}
+
+impl LineCodec
+{
+    /// Creates a codec with a maximum line length of
+    /// `DEFAULT_MAX_MESSAGE_SIZE` bytes
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Creates a codec with the given maximum line length in bytes, not
+    /// counting the line ending. Encoding or decoding a longer line fails with
+    /// `Error::MessageTooLarge`.
+    pub fn with_max_size(max_size: usize) -> Self
+    {
+        LineCodec { max_size, searched: 0 }
+    }
+
+    /// Returns the maximum line length
+    pub fn max_size(&self) -> usize
+    {
+        self.max_size
+    }
+
+    /// Checks the length of a line and converts it to a string
+    fn finish_line(&self, mut line: BytesMut) -> Result<String>
+    {
+        if line.last() == Some(&b'\r')
+        {
+            line.truncate(line.len() - 1);
+        }
+        if line.len() > self.max_size
+        {
+            return Err(Error::MessageTooLarge(line.len(), self.max_size));
+        }
+        String::from_utf8(line.to_vec()).map_err(|_| Error::InvalidUtf8)
+    }
+}
+
+impl Decoder for LineCodec
+{
+    type Item = String;
+    type Error = Error;
+
+    /// Fails with `Error::InvalidUtf8` if a line isn't valid UTF-8, or with
+    /// `Error::MessageTooLarge` as soon as more than the maximum line length
+    /// has been buffered without finding a newline.
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<String>>
+    {
+        // The buffer may have been cleared or replaced since the last call
+        let searched = self.searched.min(src.len());
+        match src[searched..].iter().position(|&b| b == b'\n')
+        {
+            Some(pos) =>
+            {
+                let mut line = src.split_to(searched + pos + 1);
+                line.truncate(line.len() - 1);
+                self.searched = 0;
+                self.finish_line(line).map(Some)
+            },
+            // Leave room for a trailing '\r'
+            None if src.len() > self.max_size.saturating_add(1) =>
+            {
+                self.searched = 0;
+                Err(Error::MessageTooLarge(src.len(), self.max_size))
+            },
+            None =>
+            {
+                self.searched = src.len();
+                Ok(None)
+            }
+        }
+    }
+
+    /// Returns any unterminated line left when the pipe reaches EOF
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<String>>
+    {
+        match self.decode(src)?
+        {
+            Some(line) => Ok(Some(line)),
+            None if src.is_empty() => Ok(None),
+            None =>
+            {
+                self.searched = 0;
+                let line = src.split();
+                self.finish_line(line).map(Some)
+            }
+        }
+    }
+}
+
+impl Encoder<&str> for LineCodec
+{
+    type Error = Error;
+
+    /// Fails with `Error::Ipipe` if the line contains a newline
+    fn encode(&mut self, line: &str, dst: &mut BytesMut) -> Result<()>
+    {
+        if line.len() > self.max_size
+        {
+            return Err(Error::MessageTooLarge(line.len(), self.max_size));
+        }
+        if line.contains('\n')
+        {
+            return Err(Error::Ipipe("Line contains a newline"));
+        }
+        dst.reserve(line.len() + 1);
+        dst.extend_from_slice(line.as_bytes());
+        dst.put_u8(b'\n');
+        Ok(())
+    }
+}
+
+impl Encoder<String> for LineCodec
+{
+    type Error = Error;
+
+    fn encode(&mut self, line: String, dst: &mut BytesMut) -> Result<()>
+    {
+        self.encode(line.as_str(), dst)
+    }
+}
+
+/// A codec for values of type `T` encoded as lines of JSON, using the same
+/// format as the `JsonLines` encoding. Requires the `serde` feature.
+#[cfg(feature="serde")]
+#[derive(Debug)]
+pub struct JsonCodec<T>
+{
+    lines: LineCodec,
+    _marker: std::marker::PhantomData<fn() -> T>
+}
+
+#[cfg(feature="serde")]
+impl<T> Default for JsonCodec<T>
+{
+    fn default() -> Self
+    {
+        Self::with_max_size(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+}
+
+#[cfg(feature="serde")]
+impl<T> Clone for JsonCodec<T>
+{
+    fn clone(&self) -> Self
+    {
+        JsonCodec { lines: self.lines.clone(), _marker: std::marker::PhantomData }
+    }
+}
+
+#[cfg(feature="serde")]
+impl<T> JsonCodec<T>
+{
+    /// Creates a codec with a maximum encoded size of
+    /// `DEFAULT_MAX_MESSAGE_SIZE` bytes per value
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Creates a codec with the given maximum encoded size per value. See
+    /// `LineCodec::with_max_size`.
+    pub fn with_max_size(max_size: usize) -> Self
+    {
+        JsonCodec { lines: LineCodec::with_max_size(max_size), _marker: std::marker::PhantomData }
+    }
+}
+
+#[cfg(feature="serde")]
+impl<T: serde::de::DeserializeOwned> Decoder for JsonCodec<T>
+{
+    type Item = T;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>>
+    {
+        self.lines.decode(src)?.map(|line| parse_json(&line)).transpose()
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<T>>
+    {
+        self.lines.decode_eof(src)?.map(|line| parse_json(&line)).transpose()
+    }
+}
+
+#[cfg(feature="serde")]
+impl<T: serde::Serialize> Encoder<T> for JsonCodec<T>
+{
+    type Error = Error;
+
+    fn encode(&mut self, value: T, dst: &mut BytesMut) -> Result<()>
+    {
+        use crate::Encoding;
+        dst.extend_from_slice(&crate::JsonLines::encode(&value, self.lines.max_size())?);
+        Ok(())
+    }
+}
+
+#[cfg(feature="serde")]
+fn parse_json<T: serde::de::DeserializeOwned>(line: &str) -> Result<T>
+{
+    serde_json::from_str(line).map_err(|e| Error::Serialization(e.to_string()))
+}
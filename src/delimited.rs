@@ -0,0 +1,276 @@
+//! This module contains a record framing layer which splits the byte stream
+//! provided by a pipe on a delimiter, such as a newline.
+
+use crate::{Error, Pipe, Result, DEFAULT_MAX_MESSAGE_SIZE};
+use std::io::{Read, Write};
+
+/// Wraps a pipe to send and receive records separated by a delimiter byte.
+/// Each record is written followed by the delimiter, so records can't contain
+/// the delimiter themselves.
+///
+/// ```
+/// use ipipe::{Pipe, DelimitedPipe};
+/// let mut pipe = DelimitedPipe::new(Pipe::create().unwrap(), b'\0');
+/// pipe.send(b"Hello").unwrap();
+/// assert_eq!(pipe.recv().unwrap().unwrap(), b"Hello");
+/// ```
+#[derive(Debug)]
+pub struct DelimitedPipe<T = Pipe>
+{
+    inner: T,
+    /// Data which has been read but not received yet
+    buffer: Vec<u8>,
+    delimiter: u8,
+    max_size: usize,
+    /// Whether the rest of a record that was too large is being skipped
+    discarding: bool
+}
+
+impl<T> DelimitedPipe<T>
+{
+    /// Wraps a pipe (or any other `Read`/`Write` type), with a maximum record
+    /// size of `DEFAULT_MAX_MESSAGE_SIZE`
+    pub fn new(inner: T, delimiter: u8) -> Self
+    {
+        Self::with_max_size(inner, delimiter, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Wraps a pipe with the given maximum record size, not counting the
+    /// delimiter. Sending or receiving a record larger than this fails with
+    /// `Error::MessageTooLarge`.
+    pub fn with_max_size(inner: T, delimiter: u8, max_size: usize) -> Self
+    {
+        DelimitedPipe { inner, buffer: Vec::new(), delimiter, max_size, discarding: false }
+    }
+
+    /// Returns the delimiter
+    pub fn delimiter(&self) -> u8
+    {
+        self.delimiter
+    }
+
+    /// Returns the maximum record size
+    pub fn max_size(&self) -> usize
+    {
+        self.max_size
+    }
+
+    /// Gets a reference to the underlying pipe
+    pub fn get_ref(&self) -> &T
+    {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying pipe. Reading directly skips
+    /// over any data that has already been buffered.
+    pub fn get_mut(&mut self) -> &mut T
+    {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying pipe. Any data that has been buffered but not
+    /// received is lost.
+    pub fn into_inner(self) -> T
+    {
+        self.inner
+    }
+}
+
+impl<T: Write> DelimitedPipe<T>
+{
+    /// Sends a record followed by the delimiter, with a single call to
+    /// `write_all`. Fails with `Error::Ipipe` if the record contains the
+    /// delimiter.
+    pub fn send(&mut self, record: &[u8]) -> Result<()>
+    {
+        if record.len() > self.max_size
+        {
+            return Err(Error::MessageTooLarge(record.len(), self.max_size));
+        }
+        if record.contains(&self.delimiter)
+        {
+            return Err(Error::Ipipe("Record contains the delimiter"));
+        }
+        let mut frame = Vec::with_capacity(record.len() + 1);
+        frame.extend_from_slice(record);
+        frame.push(self.delimiter);
+        self.inner.write_all(&frame)?;
+        Ok(())
+    }
+}
+
+impl<T: Read> DelimitedPipe<T>
+{
+    /// Receives a record without its delimiter, blocking until a whole record
+    /// is available. Returns `None` at EOF. If the pipe reaches EOF partway
+    /// through a record, the partial record is returned.
+    ///
+    /// If the record is too large, this fails with `Error::MessageTooLarge` and
+    /// the record is discarded. To bound memory use, this happens as soon as 
+    /// more than the maximum size has been read without finding a delimiter.
+    /// The rest of the record is then skipped up to the next delimiter by the
+    /// following call.
+    pub fn recv(&mut self) -> Result<Option<Vec<u8>>>
+    {
+        let mut searched = 0;
+        loop
+        {
+            if self.discarding
+            {
+                match self.buffer.iter().position(|&b| b == self.delimiter)
+                {
+                    Some(pos) => 
+                    {
+                        self.buffer.drain(..=pos);
+                        self.discarding = false;
+                    },
+                    None => self.buffer.clear()
+                }
+            }
+            if !self.discarding
+            {
+                if let Some(pos) = self.buffer[searched..].iter().position(|&b| b == self.delimiter)
+                {
+                    let mut record: Vec<u8> = self.buffer.drain(..=searched + pos).collect();
+                    record.pop();
+                    if record.len() > self.max_size
+                    {
+                        return Err(Error::MessageTooLarge(record.len(), self.max_size));
+                    }
+                    return Ok(Some(record));
+                }
+                searched = self.buffer.len();
+                if searched > self.max_size
+                {
+                    self.buffer.clear();
+                    self.discarding = true;
+                    return Err(Error::MessageTooLarge(searched, self.max_size));
+                }
+            }
+
+            let mut chunk = [0; 4096];
+            match self.inner.read(&mut chunk)
+            {
+                Ok(0) if self.buffer.is_empty() => return Ok(None),
+                Ok(0) => return Ok(Some(std::mem::take(&mut self.buffer))),
+                Ok(len) => self.buffer.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e.into())
+            }
+        }
+    }
+}
+
+/// Yields records until the pipe reaches EOF
+impl<T: Read> Iterator for DelimitedPipe<T>
+{
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        self.recv().transpose()
+    }
+}
+
+/// Wraps a pipe to send and receive lines of UTF-8 text. Lines are separated
+/// by `\n`, and a trailing `\r` is removed from received lines, as with
+/// `BufRead::lines`.
+///
+/// ```
+/// use ipipe::{Pipe, LinesPipe};
+/// let mut pipe = LinesPipe::new(Pipe::create().unwrap());
+/// pipe.send("Hello").unwrap();
+/// assert_eq!(pipe.recv().unwrap().unwrap(), "Hello");
+/// ```
+#[derive(Debug)]
+pub struct LinesPipe<T = Pipe>
+{
+    inner: DelimitedPipe<T>
+}
+
+impl<T> LinesPipe<T>
+{
+    /// Wraps a pipe (or any other `Read`/`Write` type), with a maximum line
+    /// length of `DEFAULT_MAX_MESSAGE_SIZE` bytes
+    pub fn new(inner: T) -> Self
+    {
+        Self::with_max_size(inner, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Wraps a pipe with the given maximum line length in bytes. See
+    /// `DelimitedPipe::with_max_size`.
+    pub fn with_max_size(inner: T, max_size: usize) -> Self
+    {
+        LinesPipe { inner: DelimitedPipe::with_max_size(inner, b'\n', max_size) }
+    }
+
+    /// Returns the maximum line length
+    pub fn max_size(&self) -> usize
+    {
+        self.inner.max_size()
+    }
+
+    /// Gets a reference to the underlying pipe
+    pub fn get_ref(&self) -> &T
+    {
+        self.inner.get_ref()
+    }
+
+    /// Gets a mutable reference to the underlying pipe. See
+    /// `DelimitedPipe::get_mut`.
+    pub fn get_mut(&mut self) -> &mut T
+    {
+        self.inner.get_mut()
+    }
+
+    /// Unwraps the underlying pipe. Any data that has been buffered but not
+    /// received is lost.
+    pub fn into_inner(self) -> T
+    {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: Write> LinesPipe<T>
+{
+    /// Sends a line followed by `\n`. Fails with `Error::Ipipe` if the line
+    /// contains a newline.
+    pub fn send(&mut self, line: &str) -> Result<()>
+    {
+        self.inner.send(line.as_bytes())
+    }
+}
+
+impl<T: Read> LinesPipe<T>
+{
+    /// Receives a line without its line ending, blocking until a whole line is
+    /// available. Returns `None` at EOF. Fails with `Error::InvalidUtf8` if
+    /// the line isn't valid UTF-8, in which case the line is skipped. See
+    /// `DelimitedPipe::recv`.
+    pub fn recv(&mut self) -> Result<Option<String>>
+    {
+        match self.inner.recv()?
+        {
+            Some(mut line) =>
+            {
+                if line.last() == Some(&b'\r')
+                {
+                    line.pop();
+                }
+                String::from_utf8(line).map(Some).map_err(|_| Error::InvalidUtf8)
+            },
+            None => Ok(None)
+        }
+    }
+}
+
+/// Yields lines until the pipe reaches EOF
+impl<T: Read> Iterator for LinesPipe<T>
+{
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        self.recv().transpose()
+    }
+}
@@ -4,19 +4,28 @@ use std::sync::{Arc, Weak};
 use crate::HandleType;
 
 #[cfg(unix)]
-type RawHandle = std::os::unix::io::RawFd;
+type RawHandle = std::os::fd::RawFd;
 #[cfg(windows)]
 type RawHandle = winapi::um::winnt::HANDLE;
 
+/// The owned form of a raw handle. On Unix the descriptor is closed when the 
+/// last `Arc` is dropped. On Windows, `Handle` closes it on drop instead.
+#[cfg(unix)]
+pub(crate) type OwnedHandle = std::os::fd::OwnedFd;
+#[cfg(windows)]
+pub(crate) type OwnedHandle = RawHandle;
+
 #[derive(Debug)]
 pub(crate) enum Handle
 {
-    Arc(Arc<RawHandle>, HandleType),
-    Weak(Weak<RawHandle>, HandleType)
+    Arc(Arc<OwnedHandle>, HandleType),
+    Weak(Weak<OwnedHandle>, HandleType)
 }
 
 // TODO: Determine if this is reasonable
+#[cfg(windows)]
 unsafe impl Sync for Handle {}
+#[cfg(windows)]
 unsafe impl Send for Handle {}
 
 impl Clone for Handle
@@ -33,12 +42,25 @@ impl Clone for Handle
 
 impl Handle
 {
+    /// Returns the raw handle. For a weak handle the owner may close it at any
+    /// time, so use `upgrade` instead to keep it open while it's in use.
     pub fn raw(&self) -> Option<RawHandle>
     {
         match self
         {
-            Handle::Arc(arc, _) => Some(**arc),
-            Handle::Weak(weak, _) => weak.upgrade().map(|arc| *arc),
+            Handle::Arc(arc, _) => Some(raw_handle(arc)),
+            Handle::Weak(weak, _) => weak.upgrade().map(|arc| raw_handle(&arc)),
+        }
+    }
+
+    /// Returns a strong reference to the owned handle, which keeps it from 
+    /// being closed until the reference is dropped
+    pub fn upgrade(&self) -> Option<Arc<OwnedHandle>>
+    {
+        match self
+        {
+            Handle::Arc(arc, _) => Some(arc.clone()),
+            Handle::Weak(weak, _) => weak.upgrade()
         }
     }
 
@@ -59,28 +81,66 @@ impl Handle
     }
 }
 
+#[cfg(unix)]
+impl Handle
+{
+    /// Borrows the descriptor if this handle owns it. Weak handles can't be
+    /// borrowed, since the owner could close the descriptor at any time.
+    pub fn borrow(&self) -> Option<std::os::fd::BorrowedFd<'_>>
+    {
+        use std::os::fd::AsFd;
+        match self
+        {
+            Handle::Arc(arc, _) => Some(arc.as_fd()),
+            Handle::Weak(..) => None
+        }
+    }
+
+    /// Duplicates the descriptor into a new handle which owns it, so it stays 
+    /// open regardless of what happens to this handle.
+    pub fn try_clone_owned(&self) -> std::io::Result<Handle>
+    {
+        let arc = self.upgrade()
+            .ok_or_else(|| std::io::Error::from_raw_os_error(nix::errno::Errno::EBADF as i32))?;
+        Ok(Handle::Arc(Arc::new(arc.try_clone()?), self.handle_type()))
+    }
+
+    /// Takes ownership of the descriptor. If it's shared with another owner, 
+    /// it's duplicated instead.
+    pub fn into_owned(self) -> std::io::Result<OwnedHandle>
+    {
+        match self
+        {
+            Handle::Arc(arc, _) => Arc::try_unwrap(arc).or_else(|arc| arc.try_clone()),
+            Handle::Weak(weak, _) => weak.upgrade()
+                .ok_or_else(|| std::io::Error::from_raw_os_error(nix::errno::Errno::EBADF as i32))?
+                .try_clone()
+        }
+    }
+}
+
 impl PartialEq for Handle
 {
     fn eq(&self, other: &Self) -> bool 
     {
-        match self
+        self.handle_type() == other.handle_type() 
+        && match (self.upgrade(), other.upgrade())
         {
-            Self::Arc(arc, ty) => 
-            {
-                match other
-                {
-                    Handle::Arc(arc2, ty2) => ty == ty2 && arc == arc2,
-                    Handle::Weak(weak2, ty2) => ty == ty2 && weak2.upgrade().filter(|arc2| arc == arc2).is_some(),
-                }
-            },
-            Self::Weak(weak, ty) =>
-            {
-                match other
-                {
-                    Handle::Arc(arc2, ty2) => ty == ty2 && weak.upgrade().filter(|arc| arc == arc2).is_some(),
-                    Handle::Weak(weak2, ty2) => ty == ty2 && weak.upgrade().map(|arc| weak2.upgrade().filter(|arc2| arc == *arc2)).is_some(),
-                }
-            }
+            (Some(arc), Some(arc2)) => Arc::ptr_eq(&arc, &arc2),
+            _ => false
         }
     }
 }
+
+#[cfg(unix)]
+fn raw_handle(owned: &OwnedHandle) -> RawHandle
+{
+    use std::os::fd::AsRawFd;
+    owned.as_raw_fd()
+}
+
+#[cfg(windows)]
+fn raw_handle(owned: &OwnedHandle) -> RawHandle
+{
+    *owned
+}
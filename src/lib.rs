@@ -39,9 +39,10 @@
 //!
 //! Calling `clone()` on a pipe will create a pipe who's handle exists as a Weak
 //! reference to the original pipe. That means dropping the original pipe will 
-//! also close all of its clones. If a clone is in the middle of a read or write
-//! when the drop of the original pipe happens, the pipe will not be closed
-//! until that read or write is complete. 
+//! also close all of its clones. A clone holds a strong reference to the handle
+//! for the duration of each read or write, so if it's in the middle of one when
+//! the original pipe is dropped, the handle isn't closed until that read or 
+//! write is complete.
 
 #[cfg(unix)]
 mod pipe_unix;
@@ -53,24 +54,50 @@ mod pipe_windows;
 #[cfg(windows)]
 pub use pipe_windows::*;
 
+#[cfg(unix)]
+mod pipe_set;
+#[cfg(unix)]
+pub use pipe_set::*;
+
+#[cfg(all(unix, feature="tokio"))]
+mod async_pipe;
+#[cfg(all(unix, feature="tokio"))]
+pub use async_pipe::*;
+
+#[cfg(feature="codec")]
+mod codec;
+#[cfg(feature="codec")]
+pub use codec::*;
+
+#[cfg(any(feature="channels", feature="tokio_channels"))]
+mod channels;
+
 #[cfg(feature="static_pipe")]
 #[macro_use]
 mod static_pipe;
 #[cfg(feature="static_pipe")]
 pub use static_pipe::*;
 
+mod message;
+pub use message::*;
+
+mod peekable;
+pub use peekable::*;
+
+mod delimited;
+pub use delimited::*;
+
+#[cfg(feature="serde")]
+mod typed;
+#[cfg(feature="serde")]
+pub use typed::*;
+
 #[cfg(test)]
 mod tests;
 mod handle;
 pub(crate) use handle::*;
 
-#[cfg(all(feature="channels", not(feature="tokio_channels")))]
-use std::sync::mpsc;
-
-#[cfg(all(feature="tokio_channels", not(feature="channels")))]
-use tokio::sync::mpsc;
-
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum OnCleanup
 {
     Delete,
@@ -90,98 +117,185 @@ impl Pipe
     {
         self.path().file_name()
     }
+}
 
-    /// Creates a receiver which all output from this pipe is directed into. A
-    /// thread is spawned to read from the pipe, which will shutdown when the 
-    /// receiver is dropped. Note that the thread blocks, and may attempt to read
-    /// from the pipe one time after the receiver is dropped.
-    #[cfg(all(feature="channels", not(feature="tokio_channels")))]
-    pub fn receiver(mut self) -> (mpsc::Receiver<u8>, std::thread::JoinHandle<()>)
+/// Standard error type used by this library
+#[derive(Debug)]
+pub enum Error
+{
+    /// A misuse of this library's API
+    Ipipe(&'static str),
+    /// The path can't be used for a pipe
+    InvalidPath,
+    /// The path exists but isn't a named pipe
+    NotAFifo(std::path::PathBuf),
+    /// Received data wasn't valid UTF-8
+    InvalidUtf8,
+    /// An IO error
+    Io(std::io::Error),
+    /// An error from the operating system, with the underlying IO error 
+    /// (which carries the raw error code) and the path of the pipe involved
+    /// (if known)
+    Os
+    {
+        kind: ErrorKind,
+        source: std::io::Error,
+        path: Option<std::path::PathBuf>
+    },
+    /// Any other error
+    Misc(String),
+    /// A message was larger than the maximum size: `(size, max)`
+    MessageTooLarge(usize, usize),
+    /// The pipe reached EOF partway through a message: `(read, expected)`
+    TruncatedMessage(usize, usize),
+    /// A value couldn't be serialized or deserialized
+    Serialization(String)
+}
+
+/// A general category of error, used to decide how to handle an `Error`. Each
+/// kind maps onto a `std::io::ErrorKind`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum ErrorKind
+{
+    /// The path exists but isn't a named pipe
+    NotAFifo,
+    /// The pipe doesn't exist
+    NotFound,
+    /// The other end of the pipe is gone (`EPIPE`, or `ENXIO` when opening)
+    Disconnected,
+    /// The operation timed out
+    TimedOut,
+    /// The operation would have blocked on a non-blocking pipe
+    WouldBlock,
+    /// The operation was interrupted by a signal and can be retried
+    Interrupted,
+    /// Permission to access the pipe was denied
+    PermissionDenied,
+    /// The pipe already exists
+    AlreadyExists,
+    /// An argument was invalid
+    InvalidInput,
+    /// Data received over the pipe was invalid
+    InvalidData,
+    /// The pipe reached EOF before the expected data was read
+    UnexpectedEof,
+    /// Any other error
+    Other
+}
+
+impl Error
+{
+    /// Returns the kind of this error
+    pub fn kind(&self) -> ErrorKind
     {
-        use std::io::Read;
-        let (tx, rx) = mpsc::channel();
-        (rx, 
-        std::thread::spawn(move ||
+        match self
         {
-            loop
-            {
-                for byte in (&mut self).bytes()
-                {
-                    tx.send(byte.unwrap()).unwrap()
-                }
-            }
-        }))
+            Error::Ipipe(_) | Error::Misc(_) => ErrorKind::Other,
+            Error::InvalidPath => ErrorKind::InvalidInput,
+            Error::NotAFifo(_) => ErrorKind::NotAFifo,
+            Error::InvalidUtf8 | Error::MessageTooLarge(..) | Error::Serialization(_) => ErrorKind::InvalidData,
+            Error::Io(err) => err.kind().into(),
+            Error::Os { kind, .. } => *kind,
+            Error::TruncatedMessage(..) => ErrorKind::UnexpectedEof
+        }
     }
 
-    /// Creates a receiver which all output from this pipe is directed into. A
-    /// task is spawned to read from the pipe, which will shutdown when the 
-    /// receiver is dropped. Note that the task blocks, and may attempt to read
-    /// from the pipe one time after the receiver is dropped.
-    #[cfg(all(feature="tokio_channels", not(feature="channels")))]
-    pub async fn receiver(mut self) -> (mpsc::UnboundedReceiver<u8>, tokio::task::JoinHandle<()>)
+    /// Returns the raw OS error code of this error, if it has one
+    pub fn raw_os_error(&self) -> Option<i32>
     {
-        use std::io::Read;
-        let (tx, rx) = mpsc::unbounded_channel();
-        (rx, 
-        tokio::task::spawn(async move
+        match self
         {
-            loop
-            {
-                for byte in (&mut self).bytes()
-                {
-                    tx.send(byte.unwrap()).unwrap()
-                }
-            }
-        }))
+            Error::Os { source, .. } => source.raw_os_error(),
+            Error::Io(err) => err.raw_os_error(),
+            _ => None
+        }
     }
 
-    /// Creates a sender which outputs all input into this pipe. A
-    /// thread is spawned to write into the pipe, which will shutdown when the 
-    /// sender is dropped.
-    #[cfg(all(feature="channels", not(feature="tokio_channels")))]
-    pub fn sender(mut self) -> (mpsc::Sender<u8>, std::thread::JoinHandle<()>)
+    /// Returns the path of the pipe involved in this error, if known
+    pub fn path(&self) -> Option<&std::path::Path>
     {
-        use std::io::Write;
-        let (tx, rx) = mpsc::channel();
-        (tx, 
-        std::thread::spawn(move ||
+        match self
         {
-            loop
-            {
-                (&mut self).write(&[rx.recv().unwrap()]).unwrap();
-            }
-        }))
+            Error::Os { path, .. } => path.as_deref(),
+            Error::NotAFifo(path) => Some(path),
+            _ => None
+        }
+    }
+
+    /// Attaches a path to an OS error which doesn't have one yet
+    pub(crate) fn with_path(self, path: &std::path::Path) -> Error
+    {
+        match self
+        {
+            Error::Os { kind, source, path: None } => Error::Os { kind, source, path: Some(path.to_path_buf()) },
+            Error::Io(err) if err.raw_os_error().is_some() => Error::Os 
+            { 
+                kind: err.kind().into(), 
+                source: err, 
+                path: Some(path.to_path_buf()) 
+            },
+            err => err
+        }
     }
 
-    /// Creates a sender which outputs all input into this pipe. A
-    /// thread is spawned to write into the pipe, which will shutdown when the 
-    /// sender is dropped.
-    #[cfg(all(feature="tokio_channels", not(feature="channels")))]
-    pub fn sender(mut self) -> (mpsc::UnboundedSender<u8>, tokio::task::JoinHandle<()>)
+    /// Creates an error from a raw OS error code
+    pub(crate) fn from_raw_os_error(errno: i32) -> Error
     {
-        use std::io::Write;
-        let (tx, mut rx) = mpsc::unbounded_channel();
-        (tx, 
-        tokio::task::spawn(async move
+        let kind = match errno
         {
-            loop
-            {
-                (&mut self).write(&[rx.recv().await.unwrap()]).unwrap();
-            }
-        }))
+            // Opening the write end of a FIFO without blocking fails with 
+            // ENXIO if there are no readers
+            #[cfg(unix)]
+            errno if errno == nix::errno::Errno::ENXIO as i32 => ErrorKind::Disconnected,
+            errno => std::io::Error::from_raw_os_error(errno).kind().into()
+        };
+        Error::Os { kind, source: std::io::Error::from_raw_os_error(errno), path: None }
     }
 }
 
-/// Standard error type used by this library
-#[derive(Debug)]
-pub enum Error
+impl From<std::io::ErrorKind> for ErrorKind
 {
-    Ipipe(&'static str),
-    InvalidPath,
-    InvalidUtf8,
-    Io(std::io::Error),
-    Native(&'static str, u32, String),
-    Misc(String)
+    fn from(kind: std::io::ErrorKind) -> ErrorKind
+    {
+        use std::io::ErrorKind as Io;
+        match kind
+        {
+            Io::NotFound => ErrorKind::NotFound,
+            Io::PermissionDenied => ErrorKind::PermissionDenied,
+            Io::AlreadyExists => ErrorKind::AlreadyExists,
+            Io::WouldBlock => ErrorKind::WouldBlock,
+            Io::TimedOut => ErrorKind::TimedOut,
+            Io::Interrupted => ErrorKind::Interrupted,
+            Io::InvalidInput => ErrorKind::InvalidInput,
+            Io::InvalidData => ErrorKind::InvalidData,
+            Io::UnexpectedEof => ErrorKind::UnexpectedEof,
+            Io::BrokenPipe | Io::ConnectionReset | Io::ConnectionAborted | Io::NotConnected => ErrorKind::Disconnected,
+            _ => ErrorKind::Other
+        }
+    }
+}
+
+impl From<ErrorKind> for std::io::ErrorKind
+{
+    fn from(kind: ErrorKind) -> std::io::ErrorKind
+    {
+        use std::io::ErrorKind as Io;
+        match kind
+        {
+            ErrorKind::NotAFifo | ErrorKind::InvalidInput => Io::InvalidInput,
+            ErrorKind::NotFound => Io::NotFound,
+            ErrorKind::Disconnected => Io::BrokenPipe,
+            ErrorKind::TimedOut => Io::TimedOut,
+            ErrorKind::WouldBlock => Io::WouldBlock,
+            ErrorKind::Interrupted => Io::Interrupted,
+            ErrorKind::PermissionDenied => Io::PermissionDenied,
+            ErrorKind::AlreadyExists => Io::AlreadyExists,
+            ErrorKind::InvalidData => Io::InvalidData,
+            ErrorKind::UnexpectedEof => Io::UnexpectedEof,
+            ErrorKind::Other => Io::Other
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -194,14 +308,29 @@ impl std::fmt::Display for Error
         {
             Error::Ipipe(s) => s.fmt(f),
             Error::InvalidPath => write!(f, "Invalid path"),
+            Error::NotAFifo(path) => write!(f, "{} is not a named pipe", path.display()),
             Error::InvalidUtf8 => write!(f, "Invalid Utf8"),
             Error::Io(err) => err.fmt(f),
-            Error::Native(text, code, oss) => write!(f, "{}: {} - {}", text, code, oss),
+            Error::Os { source, path: None, .. } => source.fmt(f),
+            Error::Os { source, path: Some(path), .. } => write!(f, "{}: {}", path.display(), source),
             Error::Misc(s) => s.fmt(f),
+            Error::MessageTooLarge(size, max) => write!(f, "Message of {} bytes exceeds the maximum size of {} bytes", size, max),
+            Error::TruncatedMessage(read, expected) => write!(f, "Message truncated after {} of {} bytes", read, expected),
+            Error::Serialization(s) => write!(f, "Serialization error: {}", s),
+        }
+    }
+}
+impl std::error::Error for Error
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+    {
+        match self
+        {
+            Error::Io(err) | Error::Os { source: err, .. } => Some(err),
+            _ => None
         }
     }
 }
-impl std::error::Error for Error{}
 
 impl From<Error> for std::io::Error
 {
@@ -210,7 +339,10 @@ impl From<Error> for std::io::Error
         match err
         {
             Error::Io(err) => err,
-            e => std::io::Error::new(std::io::ErrorKind::Other, e)
+            // Keep the raw error code where it doesn't change the error kind.
+            // Otherwise it's still available through `source`.
+            Error::Os { kind, source, .. } if source.kind() == kind.into() => source,
+            e => std::io::Error::new(e.kind().into(), e)
         }
     }
 }
@@ -244,15 +376,15 @@ impl From<nix::Error> for Error
 {
     fn from(error: nix::Error) -> Error
     {
-        Error::Native("", error as u32, error.desc().to_string())
+        Error::from_raw_os_error(error as i32)
     }
 }
 
 impl From<std::ffi::NulError> for Error
 {
-    fn from(error: std::ffi::NulError) -> Error
+    fn from(_: std::ffi::NulError) -> Error
     {
-        Error::Native("Interior null character found", error.nul_position() as u32, format!("{}", error))
+        Error::InvalidPath
     }
 }
 
@@ -0,0 +1,288 @@
+//! This module contains a message framing layer which can be used on top of 
+//! the byte stream provided by a pipe.
+
+use crate::{Error, Pipe, Result};
+use std::io::{Read, Write};
+
+/// The default maximum size of a message, in bytes
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// The size of the length prefix written before each message
+pub(crate) const HEADER_SIZE: usize = std::mem::size_of::<u32>();
+
+/// Wraps a pipe to send and receive discrete messages rather than a stream of
+/// bytes. Each message is written as a 4-byte big-endian length followed by
+/// the message itself. 
+/// 
+/// ```
+/// use ipipe::{Pipe, MessagePipe};
+/// let mut pipe = MessagePipe::new(Pipe::create().unwrap());
+/// pipe.send(b"Hello").unwrap();
+/// assert_eq!(pipe.recv().unwrap(), b"Hello");
+/// ```
+#[derive(Debug)]
+pub struct MessagePipe<T = Pipe>
+{
+    inner: T,
+    max_size: usize,
+    /// The part of the frame being received which has been read so far
+    partial: Vec<u8>
+}
+
+impl<T> MessagePipe<T>
+{
+    /// Wraps a pipe (or any other `Read`/`Write` type), with a maximum message
+    /// size of `DEFAULT_MAX_MESSAGE_SIZE`
+    pub fn new(inner: T) -> Self
+    {
+        Self::with_max_size(inner, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Wraps a pipe with the given maximum message size. Sending or receiving
+    /// a message larger than this fails with `Error::MessageTooLarge`.
+    pub fn with_max_size(inner: T, max_size: usize) -> Self
+    {
+        MessagePipe { inner, max_size: max_size.min(u32::MAX as usize), partial: Vec::new() }
+    }
+
+    /// Returns the maximum message size
+    pub fn max_size(&self) -> usize
+    {
+        self.max_size
+    }
+
+    /// Gets a reference to the underlying pipe
+    pub fn get_ref(&self) -> &T
+    {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying pipe. Reading or writing 
+    /// directly may corrupt the message stream.
+    pub fn get_mut(&mut self) -> &mut T
+    {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying pipe. Any part of a message that has been read
+    /// but not received yet is lost.
+    pub fn into_inner(self) -> T
+    {
+        self.inner
+    }
+
+    fn check_size(&self, size: usize) -> Result<()>
+    {
+        if size > self.max_size
+        {
+            Err(Error::MessageTooLarge(size, self.max_size))
+        }
+        else
+        {
+            Ok(())
+        }
+    }
+}
+
+impl<T: Write> MessagePipe<T>
+{
+    /// Sends a message. The length prefix and the message are written with a
+    /// single call to `write_all`.
+    pub fn send(&mut self, message: &[u8]) -> Result<()>
+    {
+        self.check_size(message.len())?;
+        self.inner.write_all(&encode_frame(message))?;
+        Ok(())
+    }
+}
+
+impl<T: Read> MessagePipe<T>
+{
+    /// Receives a message, blocking until a whole message is available. If the
+    /// pipe reaches EOF before any part of a message is read, this fails with
+    /// an `UnexpectedEof` IO error. If it reaches EOF partway through a 
+    /// message, this fails with `Error::TruncatedMessage`. 
+    /// 
+    /// If reading fails partway through a message, for example with 
+    /// `ErrorKind::TimedOut` because of a read timeout or with 
+    /// `ErrorKind::WouldBlock` on a non-blocking pipe, the part that has been
+    /// read is kept and the next call picks up where this one left off.
+    /// 
+    /// If the message is too large, the message is not read, and the stream 
+    /// can no longer be used.
+    pub fn recv(&mut self) -> Result<Vec<u8>>
+    {
+        self.fill_partial(HEADER_SIZE)?;
+        let mut header = [0; HEADER_SIZE];
+        header.copy_from_slice(&self.partial[..HEADER_SIZE]);
+        let size = u32::from_be_bytes(header) as usize;
+        self.check_size(size)?;
+
+        self.fill_partial(HEADER_SIZE + size)?;
+        let message = self.partial.split_off(HEADER_SIZE);
+        self.partial.clear();
+        Ok(message)
+    }
+
+    /// Reads until the partial frame is `len` bytes long. If an error 
+    /// interrupts this, whatever has been read is kept for the next call.
+    fn fill_partial(&mut self, len: usize) -> Result<()>
+    {
+        while self.partial.len() < len
+        {
+            let start = self.partial.len();
+            self.partial.resize(len, 0);
+            let result = self.inner.read(&mut self.partial[start..]);
+            self.partial.truncate(start + result.as_ref().map_or(0, |&read| read));
+            match result
+            {
+                Ok(0) if start == 0 => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+                Ok(0) => 
+                {
+                    // The rest of the frame is never coming
+                    self.partial.clear();
+                    return Err(Error::TruncatedMessage(start, len));
+                },
+                Ok(_) => {},
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e.into())
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The largest write the OS guarantees won't be interleaved with writes from
+/// other processes
+#[cfg(unix)]
+pub const PIPE_BUF: usize = nix::libc::PIPE_BUF;
+
+/// The largest record that can be written with `write_atomic`, leaving room for
+/// the length prefix
+#[cfg(unix)]
+pub const MAX_ATOMIC_RECORD_SIZE: usize = PIPE_BUF - HEADER_SIZE;
+
+#[cfg(unix)]
+impl Pipe
+{
+    /// Writes a record, prefixed with its length, in a single write of at most 
+    /// `PIPE_BUF` bytes. The OS guarantees such writes are never interleaved
+    /// with writes from other processes, so many writers can safely share a 
+    /// pipe as long as they all use this function. Records larger than 
+    /// `MAX_ATOMIC_RECORD_SIZE` are rejected with `Error::MessageTooLarge` 
+    /// rather than being split. Records are read with `AtomicRecordReader`.
+    pub fn write_atomic(&mut self, record: &[u8]) -> Result<()>
+    {
+        write_record(self, record)
+    }
+}
+
+#[cfg(unix)]
+impl crate::PipeWriter
+{
+    /// Writes a record in a single atomic write. See `Pipe::write_atomic`.
+    pub fn write_atomic(&mut self, record: &[u8]) -> Result<()>
+    {
+        write_record(self, record)
+    }
+}
+
+/// Writes a record with a single call to `write`
+#[cfg(unix)]
+fn write_record<W: Write>(writer: &mut W, record: &[u8]) -> Result<()>
+{
+    if record.len() > MAX_ATOMIC_RECORD_SIZE
+    {
+        return Err(Error::MessageTooLarge(record.len(), MAX_ATOMIC_RECORD_SIZE));
+    }
+    let frame = encode_frame(record);
+    loop
+    {
+        match writer.write(&frame)
+        {
+            Ok(len) if len == frame.len() => return Ok(()),
+            // Writes of at most PIPE_BUF bytes are all or nothing, so this 
+            // means the writer isn't a pipe
+            Ok(len) => return Err(Error::TruncatedMessage(len, frame.len())),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e.into())
+        }
+    }
+}
+
+/// Reads records written with `Pipe::write_atomic`. Since each record is 
+/// written atomically, records from many writers can be read from the same
+/// pipe without being interleaved.
+/// 
+/// ```
+/// use ipipe::{Pipe, AtomicRecordReader};
+/// let mut pipe = Pipe::create().unwrap();
+/// pipe.write_atomic(b"Hello").unwrap();
+/// let mut reader = AtomicRecordReader::new(pipe);
+/// assert_eq!(reader.recv().unwrap(), b"Hello");
+/// ```
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct AtomicRecordReader<T = Pipe>
+{
+    inner: MessagePipe<T>
+}
+
+#[cfg(unix)]
+impl<T> AtomicRecordReader<T>
+{
+    /// Wraps a pipe (or any other `Read` type)
+    pub fn new(inner: T) -> Self
+    {
+        AtomicRecordReader { inner: MessagePipe::with_max_size(inner, MAX_ATOMIC_RECORD_SIZE) }
+    }
+
+    /// Gets a reference to the underlying pipe
+    pub fn get_ref(&self) -> &T
+    {
+        self.inner.get_ref()
+    }
+
+    /// Unwraps the underlying pipe
+    pub fn into_inner(self) -> T
+    {
+        self.inner.into_inner()
+    }
+}
+
+#[cfg(unix)]
+impl<T: Read> AtomicRecordReader<T>
+{
+    /// Receives a record, blocking until one is available. Fails in the same
+    /// ways as `MessagePipe::recv`.
+    pub fn recv(&mut self) -> Result<Vec<u8>>
+    {
+        self.inner.recv()
+    }
+}
+
+/// Yields records until the pipe reaches EOF
+#[cfg(unix)]
+impl<T: Read> Iterator for AtomicRecordReader<T>
+{
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        match self.recv()
+        {
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            result => Some(result)
+        }
+    }
+}
+
+/// Prefixes a message with its length. The caller is responsible for checking
+/// the message size.
+pub(crate) fn encode_frame(message: &[u8]) -> Vec<u8>
+{
+    let mut frame = Vec::with_capacity(HEADER_SIZE + message.len());
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(message);
+    frame
+}
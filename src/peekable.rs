@@ -0,0 +1,177 @@
+//! This module contains a wrapper which allows data to be peeked at before it
+//! is read from a pipe.
+
+use crate::Pipe;
+use std::io::{Read, Write};
+
+/// Wraps a pipe to allow peeking at incoming data without consuming it. Pipes
+/// don't support peeking directly, so peeked data is read from the pipe and 
+/// held in a buffer until it's read from the wrapper.
+/// 
+/// ```
+/// use ipipe::{Pipe, PeekablePipe};
+/// use std::io::{Read, Write};
+/// let mut pipe = PeekablePipe::new(Pipe::create().unwrap());
+/// pipe.write_all(b"Hello").unwrap();
+/// let mut buf = [0; 5];
+/// assert_eq!(pipe.peek(&mut buf).unwrap(), 5);
+/// pipe.read_exact(&mut buf).unwrap();
+/// assert_eq!(&buf, b"Hello");
+/// ```
+#[derive(Debug)]
+pub struct PeekablePipe<T = Pipe>
+{
+    inner: T,
+    buffer: Vec<u8>,
+    pos: usize
+}
+
+impl<T> PeekablePipe<T>
+{
+    /// Wraps a pipe (or any other `Read` type)
+    pub fn new(inner: T) -> Self
+    {
+        PeekablePipe { inner, buffer: Vec::new(), pos: 0 }
+    }
+
+    /// Returns the data which has been peeked but not read yet
+    pub fn buffer(&self) -> &[u8]
+    {
+        &self.buffer[self.pos..]
+    }
+
+    /// Gets a reference to the underlying pipe
+    pub fn get_ref(&self) -> &T
+    {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying pipe. Reading from it 
+    /// directly skips over any peeked data.
+    pub fn get_mut(&mut self) -> &mut T
+    {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying pipe. Any peeked data that hasn't been read is
+    /// lost.
+    pub fn into_inner(self) -> T
+    {
+        self.inner
+    }
+}
+
+impl<T: Read + Available> PeekablePipe<T>
+{
+    /// Copies incoming data into `buf` without consuming it, returning the 
+    /// number of bytes copied. If no data has been peeked yet, this blocks 
+    /// like `read` until some data is available. If fewer than `buf.len()` 
+    /// bytes have been peeked, more are read from the pipe, but only as much
+    /// as can be read without blocking. This means a header can be peeked, 
+    /// and then the whole frame once `available` shows it has arrived. 
+    /// Returns 0 at EOF.
+    pub fn peek(&mut self, buf: &mut [u8]) -> std::io::Result<usize>
+    {
+        if self.pos == self.buffer.len() && !buf.is_empty()
+        {
+            self.buffer.clear();
+            self.pos = 0;
+            self.fill(buf.len())?;
+        }
+        let wanted = buf.len().saturating_sub(self.buffer.len() - self.pos);
+        if wanted > 0
+        {
+            let available = self.inner.available()?;
+            if available > 0
+            {
+                // Move the unread data to the front so the buffer doesn't grow
+                self.buffer.drain(..self.pos);
+                self.pos = 0;
+                self.fill(wanted.min(available))?;
+            }
+        }
+        let len = buf.len().min(self.buffer.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+        Ok(len)
+    }
+
+    /// Returns the number of bytes that can be read without blocking, 
+    /// including peeked data
+    pub fn available(&mut self) -> std::io::Result<usize>
+    {
+        Ok(self.buffer().len() + self.inner.available()?)
+    }
+
+    /// Reads up to `len` more bytes from the pipe onto the end of the buffer
+    fn fill(&mut self, len: usize) -> std::io::Result<()>
+    {
+        let start = self.buffer.len();
+        self.buffer.resize(start + len, 0);
+        let read = loop
+        {
+            match self.inner.read(&mut self.buffer[start..])
+            {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+                result => break result
+            }
+        };
+        self.buffer.truncate(start + read.as_ref().map_or(0, |&read| read));
+        read.map(drop)
+    }
+}
+
+/// A pipe which can report how many bytes can be read from it without 
+/// blocking, which lets `PeekablePipe::peek` read more without blocking
+pub trait Available
+{
+    /// Returns the number of bytes that can be read without blocking
+    fn available(&mut self) -> std::io::Result<usize>;
+}
+
+#[cfg(unix)]
+impl Available for Pipe
+{
+    /// See `Pipe::available`
+    fn available(&mut self) -> std::io::Result<usize>
+    {
+        Pipe::available(self)
+    }
+}
+
+#[cfg(unix)]
+impl Available for crate::PipeReader
+{
+    /// See `PipeReader::available`
+    fn available(&mut self) -> std::io::Result<usize>
+    {
+        crate::PipeReader::available(self)
+    }
+}
+
+impl<T: Read> Read for PeekablePipe<T>
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize>
+    {
+        if self.pos == self.buffer.len()
+        {
+            return self.inner.read(buf);
+        }
+        let len = buf.len().min(self.buffer.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl<T: Write> Write for PeekablePipe<T>
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+    {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>
+    {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,194 @@
+//! This module contains `PipeSet`, which waits on many pipes at once.
+
+use crate::{Direction, Pipe, Result};
+use crate::pipe_unix::{poll_until, RETRY_INTERVAL};
+use nix::poll::{PollFd, PollFlags};
+use std::time::{Duration, Instant};
+use std::os::unix::io::AsRawFd;
+
+/// A set of pipes which can be waited on together with `poll(2)`, so many 
+/// pipes can be served from a single thread. Each pipe is identified by a key
+/// chosen by the caller and is watched in the given direction.
+/// 
+/// ```
+/// use ipipe::{Direction, Pipe, PipeSet};
+/// use std::io::Write;
+/// let mut pipe = Pipe::create().unwrap();
+/// pipe.write_all(b"X").unwrap();
+/// 
+/// let mut set = PipeSet::new();
+/// set.insert("logs", pipe, Direction::Read);
+/// let ready = set.wait(None).unwrap();
+/// assert_eq!(ready[0].0, "logs");
+/// assert!(ready[0].1.readable);
+/// ```
+#[derive(Debug)]
+pub struct PipeSet<K>
+{
+    pipes: Vec<(K, Pipe, Direction)>
+}
+
+/// Which events a pipe in a `PipeSet` is ready for
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Readiness
+{
+    /// Data can be read without blocking
+    pub readable: bool,
+    /// Data can be written without blocking
+    pub writable: bool,
+    /// The other end of the pipe has been closed
+    pub hangup: bool,
+    /// An error is pending on the pipe
+    pub error: bool
+}
+
+impl<K> Default for PipeSet<K>
+{
+    fn default() -> Self
+    {
+        PipeSet { pipes: Vec::new() }
+    }
+}
+
+impl<K: PartialEq + Clone> PipeSet<K>
+{
+    /// Creates an empty set
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Adds a pipe to the set, watching it for reads, writes, or both. If the
+    /// key is already in use, the pipe it refers to is replaced and returned.
+    pub fn insert(&mut self, key: K, pipe: Pipe, direction: Direction) -> Option<Pipe>
+    {
+        match self.pipes.iter_mut().find(|(k, _, _)| *k == key)
+        {
+            Some(entry) => 
+            {
+                entry.2 = direction;
+                Some(std::mem::replace(&mut entry.1, pipe))
+            },
+            None => 
+            {
+                self.pipes.push((key, pipe, direction));
+                None
+            }
+        }
+    }
+
+    /// Removes a pipe from the set and returns it
+    pub fn remove(&mut self, key: &K) -> Option<Pipe>
+    {
+        let index = self.pipes.iter().position(|(k, _, _)| k == key)?;
+        Some(self.pipes.remove(index).1)
+    }
+
+    /// Returns the pipe with the given key
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut Pipe>
+    {
+        self.pipes.iter_mut().find(|(k, _, _)| k == key).map(|(_, pipe, _)| pipe)
+    }
+
+    /// Returns the number of pipes in the set
+    pub fn len(&self) -> usize
+    {
+        self.pipes.len()
+    }
+
+    /// Returns true if the set contains no pipes
+    pub fn is_empty(&self) -> bool
+    {
+        self.pipes.is_empty()
+    }
+
+    /// Waits until at least one pipe is ready, then returns the key and 
+    /// readiness of every ready pipe. Returns an empty list if the timeout
+    /// elapses first, or waits indefinitely if the timeout is `None`. 
+    /// 
+    /// Handles which haven't been opened yet are opened first, without 
+    /// blocking. A strict pipe watched for writing can't be opened until it 
+    /// has a reader, so until then it isn't ready, and opening it is retried
+    /// while waiting.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<Vec<(K, Readiness)>>
+    {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop
+        {
+            // Each entry has one or two descriptors, depending on its direction
+            let mut fds = Vec::with_capacity(self.pipes.len());
+            let mut owners = Vec::with_capacity(self.pipes.len());
+            // Keeps the descriptors of cloned pipes open while polling
+            let mut handles = Vec::with_capacity(self.pipes.len());
+            let mut pending = false;
+            for (index, (_, pipe, direction)) in self.pipes.iter_mut().enumerate()
+            {
+                if *direction != Direction::Write
+                {
+                    let handle = pipe.read_handle()?;
+                    fds.push(PollFd::new(handle.as_raw_fd(), PollFlags::POLLIN));
+                    owners.push(index);
+                    handles.push(handle);
+                }
+                if *direction != Direction::Read
+                {
+                    match pipe.try_write_handle()?
+                    {
+                        Some(handle) => 
+                        {
+                            fds.push(PollFd::new(handle.as_raw_fd(), PollFlags::POLLOUT));
+                            owners.push(index);
+                            handles.push(handle);
+                        },
+                        None => pending = true
+                    }
+                }
+            }
+
+            // Wake up periodically to retry opening write handles
+            let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let poll_timeout = match remaining
+            {
+                _ if !pending => remaining,
+                Some(remaining) => Some(remaining.min(RETRY_INTERVAL)),
+                None => Some(RETRY_INTERVAL)
+            };
+            poll_until(&mut fds, poll_timeout)?;
+
+            let ready = self.collect_ready(&fds, &owners);
+            if !ready.is_empty() || !pending || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Ok(ready);
+            }
+        }
+    }
+
+    /// Returns the key and readiness of every pipe with events in `fds`, 
+    /// where `owners` holds the index of the pipe each descriptor belongs to
+    fn collect_ready(&self, fds: &[PollFd], owners: &[usize]) -> Vec<(K, Readiness)>
+    {
+        let mut ready: Vec<(usize, Readiness)> = Vec::new();
+        for (fd, &index) in fds.iter().zip(owners)
+        {
+            let events = match fd.revents()
+            {
+                Some(events) if !events.is_empty() => events,
+                _ => continue
+            };
+            let readiness = match ready.last_mut()
+            {
+                Some((last, readiness)) if *last == index => readiness,
+                _ => 
+                {
+                    ready.push((index, Readiness::default()));
+                    &mut ready.last_mut().unwrap().1
+                }
+            };
+            readiness.readable |= events.contains(PollFlags::POLLIN);
+            readiness.writable |= events.contains(PollFlags::POLLOUT);
+            readiness.hangup |= events.contains(PollFlags::POLLHUP);
+            readiness.error |= events.intersects(PollFlags::POLLERR | PollFlags::POLLNVAL);
+        }
+        ready.into_iter().map(|(index, readiness)| (self.pipes[index].0.clone(), readiness)).collect()
+    }
+}
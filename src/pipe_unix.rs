@@ -1,114 +1,696 @@
 use super::{Result, Error, OnCleanup, Handle};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use std::os::fd::{RawFd, OwnedFd, BorrowedFd, AsFd, AsRawFd, FromRawFd, IntoRawFd};
 use fcntl::OFlag;
 use nix::{fcntl, unistd};
 use nix::sys::stat::{stat, Mode, SFlag};
+use nix::sys::uio;
 use nix::errno::Errno;
-use nix::sys::termios::{tcflush, FlushArg};
+use nix::poll::{poll, PollFd, PollFlags};
 
 #[cfg(feature="rand")]
 use rand::{thread_rng, Rng, distributions::Alphanumeric};
 
 /// Abstraction over a named pipe
+#[derive(Debug)]
 pub struct Pipe
 {
     handle1: Handle,
     handle2: Option<Handle>,
     pub(super) path: PathBuf,
     pub(super) is_slave: bool,
-    delete: OnCleanup
+    delete: OnCleanup,
+    flags: OFlag,
+    strict: bool,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    /// A capacity to set once the first handle is opened
+    #[cfg(any(target_os="linux", target_os="android"))]
+    pending_capacity: Option<usize>
 }
 
-impl Pipe
+/// The direction a pipe is opened in. This determines the access mode passed
+/// to `open(2)` for the pipe's handles.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Direction
 {
-    /// Open or create a pipe. If on_cleanup is set to 'DeleteOnDrop' the named
-    /// pipe will be deleted when the returned struct is deallocated.
-    /// Note that this function is not platform-agnostic as unix pipe paths and 
-    /// Windows pipe paths are formatted differnetly.
-    pub fn open(path: &Path, on_cleanup: OnCleanup) -> Result<Self>
+    /// Open with `O_RDONLY`. Only reads are permitted.
+    Read,
+    /// Open with `O_WRONLY`. Only writes are permitted.
+    Write,
+    /// Open with `O_RDWR`. This is the default.
+    ReadWrite
+}
+
+/// Builder used to configure how a pipe is created and opened.
+/// 
+/// ```
+/// use ipipe::PipeBuilder;
+/// let pipe = PipeBuilder::new()
+///     .name("builder_doc_pipe")
+///     .directory("/tmp")
+///     .mode(0o600)
+///     .open()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct PipeBuilder
+{
+    path: Option<PathBuf>,
+    name: Option<String>,
+    directory: PathBuf,
+    mode: Mode,
+    direction: Direction,
+    nonblocking: bool,
+    on_cleanup: OnCleanup,
+    create: bool,
+    strict: bool,
+    #[cfg(any(target_os="linux", target_os="android"))]
+    capacity: Option<usize>
+}
+
+impl Default for PipeBuilder
+{
+    fn default() -> Self
     {
-        let mode = Mode::S_IWUSR | Mode::S_IRUSR 
-                 | Mode::S_IRGRP | Mode::S_IWGRP;
-        
-        if let Some(_) = path.parent()
+        PipeBuilder
         {
-            match stat(path)
+            path: None,
+            name: None,
+            directory: PathBuf::from("/tmp"),
+            mode: Mode::S_IWUSR | Mode::S_IRUSR 
+                | Mode::S_IRGRP | Mode::S_IWGRP,
+            direction: Direction::ReadWrite,
+            nonblocking: false,
+            on_cleanup: OnCleanup::NoDelete,
+            create: true,
+            strict: false,
+            #[cfg(any(target_os="linux", target_os="android"))]
+            capacity: None
+        }
+    }
+}
+
+impl PipeBuilder
+{
+    /// Creates a builder with the default options: read-write, blocking, 
+    /// created in `/tmp` with mode `0o660` if it doesn't exist, and not 
+    /// deleted on drop.
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Sets an exact path for the pipe. This overrides `name` and `directory`.
+    pub fn path<P: AsRef<Path>>(mut self, path: P) -> Self
+    {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the name of the pipe. The pipe is created within `directory`. If
+    /// no name is set, a random one is generated (requires the `rand` 
+    /// feature).
+    pub fn name(mut self, name: &str) -> Self
+    {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Sets the directory the pipe is created in. Defaults to `/tmp`.
+    pub fn directory<P: AsRef<Path>>(mut self, directory: P) -> Self
+    {
+        self.directory = directory.as_ref().to_path_buf();
+        self
+    }
+
+    /// Sets the permission bits used when the pipe is created. Note that the 
+    /// process umask is still applied by `mkfifo(3)`. Defaults to `0o660`.
+    pub fn mode(mut self, mode: u32) -> Self
+    {
+        self.mode = Mode::from_bits_truncate(mode as nix::libc::mode_t);
+        self
+    }
+
+    /// Sets the direction the pipe is opened in. Note that opening a pipe
+    /// read-only or write-only will block until the other end is opened,
+    /// unless `nonblocking` is set.
+    pub fn direction(mut self, direction: Direction) -> Self
+    {
+        self.direction = direction;
+        self
+    }
+
+    /// Opens the pipe with `O_NONBLOCK`.
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self
+    {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Sets what happens to the pipe file when the pipe is dropped.
+    pub fn on_cleanup(mut self, on_cleanup: OnCleanup) -> Self
+    {
+        self.on_cleanup = on_cleanup;
+        self
+    }
+
+    /// If true (the default), the pipe is created if it doesn't exist. If
+    /// false, opening a pipe that doesn't exist is an error.
+    pub fn create(mut self, create: bool) -> Self
+    {
+        self.create = create;
+        self
+    }
+
+    /// Enables strict mode. By default every handle is opened with `O_RDWR`,
+    /// which keeps the pipe open even when the other side goes away. In strict
+    /// mode, read handles are opened with `O_RDONLY` and write handles with 
+    /// `O_WRONLY` the first time they are used. This means `read` returns 
+    /// `Ok(0)` once the last writer disconnects and `write` fails with 
+    /// `ErrorKind::BrokenPipe` once no readers remain. 
+    /// 
+    /// Note that, per `open(2)`, the first read or write will block until the
+    /// other side of the pipe is opened (unless `nonblocking` is set).
+    pub fn strict(mut self, strict: bool) -> Self
+    {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the capacity of the pipe's kernel buffer, in bytes. See 
+    /// `Pipe::set_capacity`. 
+    #[cfg(any(target_os="linux", target_os="android"))]
+    pub fn capacity(mut self, capacity: usize) -> Self
+    {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Opens the pipe with the configured options as an `AsyncPipe`. The pipe
+    /// is always opened in non-blocking mode, and `strict` is ignored. Must be
+    /// called from within a tokio runtime with IO enabled.
+    #[cfg(feature="tokio")]
+    pub fn open_async(self) -> Result<crate::AsyncPipe>
+    {
+        crate::AsyncPipe::from_pipe(self.nonblocking(true).strict(false).open()?)
+    }
+
+    /// Opens the pipe with the configured options.
+    pub fn open(self) -> Result<Pipe>
+    {
+        let path = self.resolve_path()?;
+        if path.parent().is_none()
+        {
+            return Err(Error::InvalidPath);
+        }
+
+        match stat(&path)
+        {
+            Ok(file_stat) => 
             {
-                Ok(file_stat) => 
+                // Error out if file is not a named pipe
+                if file_stat.st_mode & SFlag::S_IFIFO.bits() == 0
                 {
-                    // Error out if file is not a named pipe
-                    if file_stat.st_mode & SFlag::S_IFIFO.bits() == 0
-                    {
-                        Err(Error::InvalidPath)?;
-                    }
-                },
-                Err(Errno::ENOENT) => 
-                {
-                    unistd::mkfifo(path, mode)?;
-                },
-                err => 
-                {
-                    err?;
+                    Err(Error::NotAFifo(path.clone()))?;
                 }
+            },
+            Err(Errno::ENOENT) if self.create => 
+            {
+                unistd::mkfifo(&path, self.mode).map_err(|e| Error::from(e).with_path(&path))?;
+            },
+            err => 
+            {
+                err.map_err(|e| Error::from(e).with_path(&path))?;
             }
+        }
 
-            Pipe::init_handle(path)
-                .map(|handle| Pipe 
-                { 
-                    handle1: handle, 
-                    handle2: None,
-                    path: path.to_path_buf(), 
-                    is_slave: false,
-                    delete: on_cleanup
-                })
+        let flags = self.flags();
+        let mut handle = if self.strict && self.direction == Direction::ReadWrite
+        {
+            // The direction of the first handle isn't known until it's used
+            Handle::Weak(Weak::new(), HandleType::Unknown)
         }
         else
         {
-            Err(Error::InvalidPath)
+            Pipe::init_handle(&path, flags)?
+        };
+        match self.direction
+        {
+            Direction::Read => handle.set_type(HandleType::Read),
+            Direction::Write => handle.set_type(HandleType::Write),
+            Direction::ReadWrite => {}
+        }
+        let mut pipe = Pipe 
+        { 
+            handle1: handle, 
+            handle2: None,
+            path, 
+            is_slave: false,
+            delete: self.on_cleanup,
+            flags,
+            strict: self.strict,
+            read_timeout: None,
+            write_timeout: None,
+            #[cfg(any(target_os="linux", target_os="android"))]
+            pending_capacity: None
+        };
+        #[cfg(any(target_os="linux", target_os="android"))]
+        if let Some(capacity) = self.capacity
+        {
+            pipe.set_capacity(capacity)?;
+        }
+        Ok(pipe)
+    }
+
+    fn resolve_path(&self) -> Result<PathBuf>
+    {
+        if let Some(path) = &self.path
+        {
+            Ok(path.clone())
+        }
+        else if let Some(name) = &self.name
+        {
+            Ok(self.directory.join(name))
+        }
+        else
+        {
+            self.random_path()
+        }
+    }
+
+    #[cfg(feature="rand")]
+    fn random_path(&self) -> Result<PathBuf>
+    {
+        // Generate a random path name
+        Ok(self.directory.join(format!("pipe_{}_{}", std::process::id(), thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(10)
+            .collect::<String>())))
+    }
+
+    #[cfg(not(feature="rand"))]
+    fn random_path(&self) -> Result<PathBuf>
+    {
+        Err(Error::InvalidPath)
+    }
+
+    fn flags(&self) -> OFlag
+    {
+        let mut flags = match self.direction
+        {
+            Direction::Read => OFlag::O_RDONLY,
+            Direction::Write => OFlag::O_WRONLY,
+            Direction::ReadWrite => OFlag::O_RDWR
+        } | OFlag::O_NOCTTY;
+        if self.nonblocking
+        {
+            flags |= OFlag::O_NONBLOCK;
         }
+        flags
+    }
+}
+
+impl Pipe
+{
+    /// Open or create a pipe. If on_cleanup is set to 'DeleteOnDrop' the named
+    /// pipe will be deleted when the returned struct is deallocated.
+    /// Note that this function is not platform-agnostic as unix pipe paths and 
+    /// Windows pipe paths are formatted differnetly.
+    pub fn open(path: &Path, on_cleanup: OnCleanup) -> Result<Self>
+    {
+        PipeBuilder::new()
+            .path(path)
+            .on_cleanup(on_cleanup)
+            .open()
     }
 
     /// Open or create a pipe with the given name. Note that this is just a
     /// string name, not a path.
     pub fn with_name(name: &str) -> Result<Self>
     {
-        let path = PathBuf::from(format!("/tmp/{}", name));
-        Pipe::open(&path, OnCleanup::NoDelete)
+        PipeBuilder::new().name(name).open()
     }
 
     /// Create a pipe with a randomly generated name in a tempory directory.
     #[cfg(feature="rand")]
     pub fn create() -> Result<Self>
     {
-        // Generate a random path name
-        let path = PathBuf::from(format!("/tmp/pipe_{}_{}", std::process::id(), thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(10)
-            .collect::<String>()));
+        PipeBuilder::new().open()
+    }
 
-        Pipe::open(&path, OnCleanup::NoDelete)
+    /// Returns a builder which can be used to configure how a pipe is opened.
+    pub fn builder() -> PipeBuilder
+    {
+        PipeBuilder::new()
     }
 
-    /// Close a named pipe
-    pub fn close(self) -> Result<()>
+    /// Returns the handle opened alongside the pipe
+    #[cfg(feature="tokio")]
+    pub(crate) fn first_handle(&self) -> Result<Arc<OwnedFd>>
     {
-        if let Some(raw) = self.handle1.raw()
+        self.handle1.upgrade().ok_or(Errno::EBADF).map_err(Error::from)
+    }
+
+    /// Close a named pipe. Both the read and write handles are closed, and an
+    /// error closing either is returned. Handles are also closed when the 
+    /// pipe is dropped, but errors are ignored. Closing a clone only releases
+    /// that clone's reference to the handles.
+    pub fn close(mut self) -> Result<()>
+    {
+        let handle1 = std::mem::replace(&mut self.handle1, Handle::Weak(Weak::new(), HandleType::Unknown));
+        let result1 = close_handle(handle1);
+        let result2 = self.handle2.take().map_or(Ok(()), close_handle);
+        result1.and(result2)
+    }
+
+    /// Sets the read timeout. If a read doesn't receive any data within the 
+    /// timeout, it fails with `ErrorKind::TimedOut`. A timeout of `None` 
+    /// (the default) means reads block indefinitely. Passing a zero 
+    /// duration is an error. For a strict pipe, waiting for a writer to open
+    /// the pipe is bounded by the timeout too.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()>
+    {
+        self.read_timeout = check_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Sets the write timeout. If the pipe doesn't become writable within the
+    /// timeout, the write fails with `ErrorKind::TimedOut`. A timeout of 
+    /// `None` (the default) means writes block indefinitely. Passing a zero 
+    /// duration is an error. For a strict pipe, waiting for a reader to open
+    /// the pipe is bounded by the timeout too.
+    ///
+    /// While a write timeout is set, each write writes at most `PIPE_BUF` 
+    /// bytes so that it can't block once the pipe is writable. `write_all` 
+    /// still writes everything, waiting up to the timeout for each chunk.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()>
+    {
+        self.write_timeout = check_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Returns the read timeout of this pipe
+    pub fn read_timeout(&self) -> Option<Duration>
+    {
+        self.read_timeout
+    }
+
+    /// Returns the write timeout of this pipe
+    pub fn write_timeout(&self) -> Option<Duration>
+    {
+        self.write_timeout
+    }
+
+    /// Moves this pipe into or out of non-blocking mode. In non-blocking mode,
+    /// reads and writes that can't complete immediately fail with 
+    /// `ErrorKind::WouldBlock` instead of blocking. This applies to both of
+    /// this pipe's handles and to any handle it opens later. Clones share 
+    /// their handles with this pipe, so they're affected too.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()>
+    {
+        self.flags.set(OFlag::O_NONBLOCK, nonblocking);
+        set_handle_nonblocking(&self.handle1, nonblocking)?;
+        self.handle2.as_ref().map_or(Ok(()), |handle| set_handle_nonblocking(handle, nonblocking))
+    }
+
+    /// Borrows the descriptor of the handle opened alongside the pipe (the 
+    /// first handle used, for strict pipes). Fails with `EBADF` if the handle
+    /// isn't open yet, or if this pipe is a clone, since a clone doesn't own
+    /// its handle. Use `try_clone` to get a pipe which does.
+    pub fn try_as_fd(&self) -> Result<BorrowedFd<'_>>
+    {
+        self.handle1.borrow().ok_or_else(|| Error::from(Errno::EBADF).with_path(&self.path))
+    }
+
+    /// Returns true, since `write_vectored` writes all buffers with a single 
+    /// `writev(2)` call. A vectored write of at most `PIPE_BUF` bytes in total
+    /// is atomic, just like a plain write. This mirrors the unstable 
+    /// `Write::is_write_vectored`.
+    pub fn is_write_vectored(&self) -> bool
+    {
+        true
+    }
+
+    /// Returns the number of bytes that can be read without blocking, using
+    /// `ioctl(FIONREAD)`. Opens the read handle if it isn't open yet.
+    pub fn available(&mut self) -> std::io::Result<usize>
+    {
+        bytes_available(self.read_handle()?.as_raw_fd())
+    }
+
+    /// Waits until the reader has consumed everything written to the pipe, by
+    /// polling `ioctl(FIONREAD)` on the write handle. Returns immediately if 
+    /// the write handle isn't open. Fails with `ErrorKind::TimedOut` if the 
+    /// pipe isn't drained within the timeout, or waits indefinitely if the 
+    /// timeout is `None`. Note that a non-strict pipe is its own reader, so 
+    /// data it has written can also be drained by reading it back.
+    pub fn drain(&mut self, timeout: Option<Duration>) -> Result<()>
+    {
+        if !self.has_handle(HandleType::Write)
+        {
+            return Ok(());
+        }
+        let handle = self.init_handle_type(HandleType::Write)?;
+        drain_handle(handle.as_raw_fd(), timeout).map_err(|e| e.with_path(&self.path))
+    }
+
+    /// Returns the capacity of the pipe's kernel buffer, in bytes. Fails if 
+    /// none of this pipe's handles are open yet. 
+    #[cfg(any(target_os="linux", target_os="android"))]
+    pub fn capacity(&self) -> Result<usize>
+    {
+        let handle = self.open_handle().ok_or(Errno::EBADF)?;
+        let capacity = fcntl::fcntl(handle.as_raw_fd(), fcntl::FcntlArg::F_GETPIPE_SZ).map_err(|e| Error::from(e).with_path(&self.path))?;
+        Ok(capacity as usize)
+    }
+
+    /// Sets the capacity of the pipe's kernel buffer, in bytes (64 KiB by 
+    /// default). The kernel rounds the capacity up to a power of two number 
+    /// of pages. Unprivileged processes can't go above 
+    /// `/proc/sys/fs/pipe-max-size`, and shrinking below the amount of data
+    /// currently buffered fails with `EBUSY`. The capacity belongs to the 
+    /// pipe rather than to this handle, so it's shared with every reader and
+    /// writer. If no handle is open yet, the capacity is set when the first
+    /// one is opened.
+    #[cfg(any(target_os="linux", target_os="android"))]
+    pub fn set_capacity(&mut self, capacity: usize) -> Result<()>
+    {
+        let capacity: nix::libc::c_int = std::convert::TryFrom::try_from(capacity).map_err(|_| Errno::EINVAL)?;
+        match self.open_handle()
         {
-            unistd::close(raw).map_err(Error::from)
+            Some(handle) => 
+            {
+                fcntl::fcntl(handle.as_raw_fd(), fcntl::FcntlArg::F_SETPIPE_SZ(capacity)).map_err(|e| Error::from(e).with_path(&self.path))?;
+            },
+            None => self.pending_capacity = Some(capacity as usize)
+        }
+        Ok(())
+    }
+
+    /// Moves up to `len` bytes from `source` into this pipe with `splice(2)`,
+    /// without copying them through userspace. `source` can be any file or
+    /// socket, and is read from its current offset. Returns the number of 
+    /// bytes moved, which is 0 once `source` reaches EOF. Waits for the pipe 
+    /// to be writable first if a write timeout is set, in which case at most
+    /// `PIPE_BUF` bytes are moved, as with `write`.
+    #[cfg(any(target_os="linux", target_os="android"))]
+    pub fn splice_from<F: AsFd>(&mut self, source: &F, len: usize) -> Result<usize>
+    {
+        let handle = self.timed_write_handle(self.write_timeout)?;
+        wait_for(handle.as_raw_fd(), PollFlags::POLLOUT, self.write_timeout)?;
+        let len = capped_write_len(len, self.write_timeout);
+        fcntl::splice(source.as_fd().as_raw_fd(), None, handle.as_raw_fd(), None, len, fcntl::SpliceFFlags::SPLICE_F_MOVE)
+            .map_err(|e| Error::from(e).with_path(&self.path))
+    }
+
+    /// Moves up to `len` bytes from this pipe into `destination` with 
+    /// `splice(2)`, without copying them through userspace. `destination` can
+    /// be any file or socket, and is written at its current offset. Returns 
+    /// the number of bytes moved. Waits for data first if a read timeout is
+    /// set.
+    #[cfg(any(target_os="linux", target_os="android"))]
+    pub fn splice_to<F: AsFd>(&mut self, destination: &F, len: usize) -> Result<usize>
+    {
+        let handle = self.timed_read_handle(self.read_timeout)?;
+        wait_for(handle.as_raw_fd(), PollFlags::POLLIN, self.read_timeout)?;
+        fcntl::splice(handle.as_raw_fd(), None, destination.as_fd().as_raw_fd(), None, len, fcntl::SpliceFFlags::SPLICE_F_MOVE)
+            .map_err(|e| Error::from(e).with_path(&self.path))
+    }
+
+    /// Copies up to `len` bytes from this pipe into another pipe with `tee(2)`,
+    /// without consuming them from this pipe or copying them through 
+    /// userspace. Returns the number of bytes copied. Waits for data first if
+    /// a read timeout is set.
+    #[cfg(any(target_os="linux", target_os="android"))]
+    pub fn tee_to(&mut self, destination: &mut Pipe, len: usize) -> Result<usize>
+    {
+        let handle = self.timed_read_handle(self.read_timeout)?;
+        wait_for(handle.as_raw_fd(), PollFlags::POLLIN, self.read_timeout)?;
+        let destination_handle = destination.init_handle_type(HandleType::Write)?;
+        fcntl::tee(handle.as_raw_fd(), destination_handle.as_raw_fd(), len, fcntl::SpliceFFlags::empty())
+            .map_err(|e| Error::from(e).with_path(&self.path))
+    }
+
+    /// Maps `buf` into this pipe with `vmsplice(2)` rather than copying it. 
+    /// Returns the number of bytes written. Waits for the pipe to be writable
+    /// first if a write timeout is set, in which case at most `PIPE_BUF` bytes
+    /// are written, as with `write`.
+    ///
+    /// # Safety
+    /// The pipe refers to the memory behind `buf` until the data is read, so
+    /// `buf` must not be modified or freed until then, or the reader may see 
+    /// the changed memory. `drain` can be used to wait for the data to be 
+    /// read.
+    #[cfg(any(target_os="linux", target_os="android"))]
+    pub unsafe fn vmsplice(&mut self, buf: &[u8]) -> Result<usize>
+    {
+        let handle = self.timed_write_handle(self.write_timeout)?;
+        wait_for(handle.as_raw_fd(), PollFlags::POLLOUT, self.write_timeout)?;
+        let buf = &buf[..capped_write_len(buf.len(), self.write_timeout)];
+        fcntl::vmsplice(handle.as_raw_fd(), &[std::io::IoSlice::new(buf)], fcntl::SpliceFFlags::empty())
+            .map_err(|e| Error::from(e).with_path(&self.path))
+    }
+
+    /// Returns any open handle
+    #[cfg(any(target_os="linux", target_os="android"))]
+    fn open_handle(&self) -> Option<Arc<OwnedFd>>
+    {
+        self.handle1.upgrade().or_else(|| self.handle2.as_ref().and_then(Handle::upgrade))
+    }
+
+    /// Sets the capacity requested before any handle was opened
+    fn apply_pending_capacity(&mut self) -> Result<()>
+    {
+        #[cfg(any(target_os="linux", target_os="android"))]
+        if let Some(capacity) = self.pending_capacity.take()
+        {
+            self.set_capacity(capacity)?;
+        }
+        Ok(())
+    }
+
+    /// Creates a new pipe with duplicates of this pipe's handles, like 
+    /// `File::try_clone`. Unlike `clone`, the new pipe owns its handles, so it
+    /// keeps working after this pipe is dropped or closed. The new pipe never
+    /// deletes the pipe file, and opens any handle this pipe hasn't opened yet
+    /// from the path when it's first needed.
+    pub fn try_clone(&self) -> Result<Pipe>
+    {
+        // An unopened strict handle is opened lazily by the new pipe instead
+        let handle1 = if self.strict && self.handle1.handle_type() == HandleType::Unknown && self.handle1.raw().is_none()
+        {
+            Handle::Weak(Weak::new(), HandleType::Unknown)
         }
         else
         {
-            Ok(())
+            self.handle1.try_clone_owned()?
+        };
+        Ok(Pipe
+        {
+            handle1,
+            handle2: self.handle2.as_ref().map(Handle::try_clone_owned).transpose()?,
+            path: self.path.clone(),
+            is_slave: false,
+            delete: OnCleanup::NoDelete,
+            flags: self.flags,
+            strict: self.strict,
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            #[cfg(any(target_os="linux", target_os="android"))]
+            pending_capacity: self.pending_capacity
+        })
+    }
+
+    /// Waits until the pipe has a reader, then opens this pipe's write handle 
+    /// if it isn't open yet. This is done by retrying a non-blocking write-only
+    /// open until it stops failing with `ENXIO`, so it works before anything
+    /// has been written. Non-strict pipes read and write through the same 
+    /// handle, so they count as their own reader and return immediately. 
+    /// Fails with `ErrorKind::TimedOut` if no reader appears within the 
+    /// timeout, or waits indefinitely if the timeout is `None`.
+    pub fn wait_for_reader(&mut self, timeout: Option<Duration>) -> Result<()>
+    {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop
+        {
+            if self.try_write_handle()?.is_some()
+            {
+                return Ok(());
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Err(Error::from(Errno::ETIMEDOUT).with_path(&self.path));
+            }
+            std::thread::sleep(RETRY_INTERVAL);
         }
     }
 
-    fn init_handle(path: &Path) -> Result<Handle>
+    /// Waits until a writer has written to the pipe or has closed its end, 
+    /// opening this pipe's read handle without blocking if it isn't open yet.
+    /// Note that a writer which has opened the pipe but hasn't written 
+    /// anything yet can't be detected. Non-strict pipes are their own writer,
+    /// so for them this waits for data. Fails with `ErrorKind::TimedOut` if 
+    /// nothing happens within the timeout, or waits indefinitely if the 
+    /// timeout is `None`.
+    pub fn wait_for_writer(&mut self, timeout: Option<Duration>) -> Result<()>
+    {
+        let handle = self.read_handle()?;
+        poll_for(handle.as_raw_fd(), PollFlags::POLLIN, timeout).map_err(|e| Error::from(e.raw_os_error().map_or(Errno::ETIMEDOUT, Errno::from_i32)).with_path(&self.path))
+    }
+
+    /// Splits this pipe into a reader and a writer, each of which owns its own
+    /// handle to the pipe. This allows each end to be moved to a different 
+    /// thread, with the direction of each end enforced by its type. If this
+    /// pipe was set to be deleted on cleanup, the file is deleted once both 
+    /// halves are dropped.
+    pub fn split(mut self) -> Result<(PipeReader, PipeWriter)>
     {
-        let mode = Mode::S_IWUSR | Mode::S_IRUSR 
-                 | Mode::S_IRGRP | Mode::S_IWGRP;
+        // The read handle is opened without blocking first, since a strict 
+        // read handle would otherwise wait for a writer that never comes
+        self.read_handle()?;
+        self.init_handle_type(HandleType::Write)?;
+        let reader = self.take_handle(HandleType::Read)?;
+        let writer = self.take_handle(HandleType::Write)?;
+        let cleanup = Arc::new(Cleanup
+        {
+            path: self.path.clone(),
+            delete: self.delete
+        });
+        self.delete = OnCleanup::NoDelete;
+        Ok((PipeReader { handle: reader, cleanup: cleanup.clone(), timeout: self.read_timeout },
+            PipeWriter { handle: writer, cleanup, timeout: self.write_timeout }))
+    }
 
-        if let Some(_) = path.parent()
+    /// Takes an initialized handle of the given type out of this pipe. If this
+    /// pipe is a slave, a new handle is opened so the caller owns it.
+    fn take_handle(&mut self, handle_type: HandleType) -> Result<Handle>
+    {
+        let handle = if self.handle1.handle_type() == handle_type
+        {
+            std::mem::replace(&mut self.handle1, Handle::Weak(Weak::new(), HandleType::Unknown))
+        }
+        else
+        {
+            self.handle2.take().ok_or(Errno::EBADF)?
+        };
+        match handle
+        {
+            Handle::Arc(..) => Ok(handle),
+            Handle::Weak(..) => 
+            {
+                let mut handle = Pipe::init_handle(&self.path, self.handle_flags(handle_type))?;
+                handle.set_type(handle_type);
+                Ok(handle)
+            }
+        }
+    }
+
+    fn init_handle(path: &Path, flags: OFlag) -> Result<Handle>
+    {
+        if path.parent().is_some()
         {
             match stat(path)
             {
@@ -117,18 +699,19 @@ impl Pipe
                     // Error out if file is not a named pipe
                     if file_stat.st_mode & SFlag::S_IFIFO.bits() == 0
                     {
-                        Err(nix::Error::ENOENT)?;
+                        Err(Error::NotAFifo(path.to_path_buf()))?;
                     }
                 },
                 err => 
                 {
-                    err?;
+                    err.map_err(|e| Error::from(e).with_path(path))?;
                 }
             }
 
-            fcntl::open(path, OFlag::O_RDWR | OFlag::O_NOCTTY, mode)
-                .map(|handle| Handle::Arc(Arc::new(handle), HandleType::Unknown))
-                .map_err(Error::from)
+            fcntl::open(path, flags, Mode::empty())
+                // Safe because the descriptor was just opened and nothing else owns it
+                .map(|handle| Handle::Arc(Arc::new(unsafe { OwnedFd::from_raw_fd(handle) }), HandleType::Unknown))
+                .map_err(|e| Error::from(e).with_path(path))
         }
         else
         {
@@ -136,25 +719,154 @@ impl Pipe
         }
     }
 
-    fn init_handle_type(&mut self, handle_type: HandleType) -> Result<std::os::unix::io::RawFd>
+    /// Returns the flags used to open a handle of the given type
+    fn handle_flags(&self, handle_type: HandleType) -> OFlag
     {
+        match handle_type
+        {
+            HandleType::Read if self.strict => (self.flags & !OFlag::O_ACCMODE) | OFlag::O_RDONLY,
+            HandleType::Write if self.strict => (self.flags & !OFlag::O_ACCMODE) | OFlag::O_WRONLY,
+            _ => self.flags
+        }
+    }
+
+    /// Returns the read handle, opening it without blocking if it isn't open 
+    /// yet. Opening a strict read handle would otherwise block until a writer
+    /// opens the pipe.
+    pub(crate) fn read_handle(&mut self) -> Result<Arc<OwnedFd>>
+    {
+        self.check_access(HandleType::Read)?;
+        if self.strict && !self.has_handle(HandleType::Read)
+        {
+            let handle = Pipe::init_handle(&self.path, self.handle_flags(HandleType::Read) | OFlag::O_NONBLOCK)?;
+            self.install_handle(handle, HandleType::Read)?;
+        }
+        self.init_handle_type(HandleType::Read)
+    }
+
+    /// Returns the write handle, opening it without blocking if it isn't open
+    /// yet. Returns `None` if this is a strict pipe without a reader, in which
+    /// case opening the write handle would block.
+    pub(crate) fn try_write_handle(&mut self) -> Result<Option<Arc<OwnedFd>>>
+    {
+        self.check_access(HandleType::Write)?;
+        if self.strict && !self.has_handle(HandleType::Write)
+        {
+            match Pipe::init_handle(&self.path, self.handle_flags(HandleType::Write) | OFlag::O_NONBLOCK)
+            {
+                Ok(handle) => self.install_handle(handle, HandleType::Write)?,
+                Err(e) if e.raw_os_error() == Some(Errno::ENXIO as i32) => return Ok(None),
+                Err(e) => return Err(e)
+            }
+        }
+        self.init_handle_type(HandleType::Write).map(Some)
+    }
+
+    /// Returns the read handle for a read with the given timeout. With a 
+    /// timeout, a strict read handle is opened without blocking so that 
+    /// waiting for a writer is bounded by the timeout too.
+    fn timed_read_handle(&mut self, timeout: Option<Duration>) -> Result<Arc<OwnedFd>>
+    {
+        match timeout
+        {
+            Some(_) => self.read_handle(),
+            None => self.init_handle_type(HandleType::Read)
+        }
+    }
+
+    /// Returns the write handle for a write with the given timeout. With a 
+    /// timeout, a strict write handle is only opened once the pipe has a 
+    /// reader, failing with `ErrorKind::TimedOut` if none appears in time.
+    fn timed_write_handle(&mut self, timeout: Option<Duration>) -> Result<Arc<OwnedFd>>
+    {
+        if timeout.is_some() && self.strict && !self.has_handle(HandleType::Write)
+        {
+            self.wait_for_reader(timeout)?;
+        }
+        self.init_handle_type(HandleType::Write)
+    }
+
+    /// Refuses to use a handle in a direction the pipe wasn't opened for
+    fn check_access(&self, handle_type: HandleType) -> Result<()>
+    {
+        let access = self.flags & OFlag::O_ACCMODE;
+        if (handle_type == HandleType::Read && access == OFlag::O_WRONLY)
+        || (handle_type == HandleType::Write && access == OFlag::O_RDONLY)
+        {
+            Err(Errno::EBADF)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether a handle for the given direction is already open
+    fn has_handle(&self, handle_type: HandleType) -> bool
+    {
+        let handle1_type = self.handle1.handle_type();
+        (self.handle1.raw().is_some() && (handle1_type == handle_type || handle1_type == HandleType::Unknown))
+        || self.handle2.as_ref().is_some_and(|handle| handle.handle_type() == handle_type)
+    }
+
+    /// Stores a handle opened with `O_NONBLOCK` as the handle for the given 
+    /// direction, restoring blocking mode if this pipe is blocking. The handle
+    /// is closed instead if this pipe already has one for that direction.
+    fn install_handle(&mut self, mut handle: Handle, handle_type: HandleType) -> Result<()>
+    {
+        if self.has_handle(handle_type)
+        {
+            return Ok(());
+        }
+        if !self.flags.contains(OFlag::O_NONBLOCK)
+        {
+            set_handle_nonblocking(&handle, false)?;
+        }
+        handle.set_type(handle_type);
+        if self.handle1.raw().is_none()
+        {
+            self.handle1 = handle;
+            self.apply_pending_capacity()?;
+        }
+        else
+        {
+            self.handle2 = Some(handle);
+        }
+        Ok(())
+    }
+
+    /// Returns the handle of the given type, opening it if needed. The 
+    /// returned `Arc` keeps the descriptor open while it's in use, even if 
+    /// this pipe is a clone and the original is dropped in the meantime.
+    fn init_handle_type(&mut self, handle_type: HandleType) -> Result<Arc<OwnedFd>>
+    {
+        self.check_access(handle_type)?;
+        // Pipes created from a descriptor have no path to open a second handle 
+        // from, so the one handle is used in both directions
+        if self.path.as_os_str().is_empty()
+        {
+            return self.handle1.upgrade().ok_or(Errno::EBADF).map_err(Error::from);
+        }
         if self.handle1.handle_type() == HandleType::Unknown
         {
+            // Strict pipes open their first handle lazily
+            if self.strict && self.handle1.raw().is_none()
+            {
+                self.handle1 = Pipe::init_handle(&self.path, self.handle_flags(handle_type))?;
+                self.apply_pending_capacity()?;
+            }
             self.handle1.set_type(handle_type);
         }
         if self.handle1.handle_type() == handle_type
         {
-            self.handle1.raw()
+            self.handle1.upgrade()
         }
         else
         {
-            if let None = self.handle2
+            if self.handle2.is_none()
             {
-                let mut handle = Pipe::init_handle(&self.path)?;
+                let mut handle = Pipe::init_handle(&self.path, self.handle_flags(handle_type))?;
                 handle.set_type(handle_type);
                 self.handle2 = Some(handle);
             }
-            self.handle2.as_ref().unwrap().raw()
+            self.handle2.as_ref().unwrap().upgrade()
         }.ok_or(nix::errno::Errno::EBADF).map_err(|e| e.into())
     }
 }
@@ -163,14 +875,21 @@ impl std::io::Write for Pipe
 {
     fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> 
     {
-        let handle = self.init_handle_type(HandleType::Write)?;
-        unistd::write(handle, bytes).map_err(Error::from).map_err(std::io::Error::from)
+        let handle = self.timed_write_handle(self.write_timeout)?;
+        write_with_timeout(handle.as_raw_fd(), bytes, self.write_timeout)
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> 
+    {
+        let handle = self.timed_write_handle(self.write_timeout)?;
+        writev_with_timeout(handle.as_raw_fd(), bufs, self.write_timeout)
     }
 
     fn flush(&mut self) -> std::io::Result<()> 
     {
-        let handle = self.init_handle_type(HandleType::Write)?;
-        tcflush(handle, FlushArg::TCOFLUSH).map_err(Error::from).map_err(std::io::Error::from)
+        // Writes go straight to the kernel, so there's nothing to flush. `drain`
+        // waits for the reader to consume what was written instead.
+        Ok(())
     }
 }
 
@@ -178,10 +897,16 @@ impl std::io::Read for Pipe
 {
     fn read(&mut self, bytes: &mut [u8]) -> std::io::Result<usize> 
     {
-        let handle = self.init_handle_type(HandleType::Read)?;
-        unistd::read(handle, bytes)
-            .map_err(Error::from)
-            .map_err(std::io::Error::from)
+        let handle = self.timed_read_handle(self.read_timeout)?;
+        wait_for(handle.as_raw_fd(), PollFlags::POLLIN, self.read_timeout)?;
+        unistd::read(handle.as_raw_fd(), bytes).map_err(std::io::Error::from)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> 
+    {
+        let handle = self.timed_read_handle(self.read_timeout)?;
+        wait_for(handle.as_raw_fd(), PollFlags::POLLIN, self.read_timeout)?;
+        uio::readv(handle.as_raw_fd(), bufs).map_err(std::io::Error::from)
     }
 }
 
@@ -195,7 +920,8 @@ impl Drop for Pipe
             self.handle2 = None;
             if let OnCleanup::Delete = self.delete
             {
-                std::fs::remove_file(&self.path).unwrap();
+                // As with the split halves, the file may already be gone
+                let _ = std::fs::remove_file(&self.path);
             }
         }
     }
@@ -204,7 +930,8 @@ impl Drop for Pipe
 impl Clone for Pipe
 {
     /// Cloning a pipe creates a slave which points to the same path but does not
-    /// close the pipe when dropped.
+    /// close the pipe when dropped. The slave stops working once the original 
+    /// pipe is dropped; use `try_clone` for a pipe that can outlive it.
     fn clone(&self) -> Self 
     {
         Pipe 
@@ -213,7 +940,496 @@ impl Clone for Pipe
             handle2: self.handle2.clone(),
             path: self.path.clone(), 
             is_slave: true,
-            delete: OnCleanup::NoDelete
+            delete: OnCleanup::NoDelete,
+            flags: self.flags,
+            strict: self.strict,
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            #[cfg(any(target_os="linux", target_os="android"))]
+            pending_capacity: None
+        }
+    }
+}
+
+/// The read half of a pipe, created by `Pipe::split`
+#[derive(Debug)]
+pub struct PipeReader
+{
+    handle: Handle,
+    cleanup: Arc<Cleanup>,
+    timeout: Option<Duration>
+}
+
+/// The write half of a pipe, created by `Pipe::split`
+#[derive(Debug)]
+pub struct PipeWriter
+{
+    handle: Handle,
+    cleanup: Arc<Cleanup>,
+    timeout: Option<Duration>
+}
+
+impl PipeReader
+{
+    /// Return the path to this named pipe
+    pub fn path(&self) -> &Path
+    {
+        &self.cleanup.path
+    }
+
+    /// Sets the read timeout. See `Pipe::set_read_timeout`.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()>
+    {
+        self.timeout = check_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Returns the read timeout of this reader
+    pub fn read_timeout(&self) -> Option<Duration>
+    {
+        self.timeout
+    }
+
+    /// Returns the number of bytes that can be read without blocking. See 
+    /// `Pipe::available`.
+    pub fn available(&self) -> std::io::Result<usize>
+    {
+        bytes_available(self.handle.raw().ok_or(Errno::EBADF)?)
+    }
+
+    /// Moves this reader into or out of non-blocking mode. See 
+    /// `Pipe::set_nonblocking`.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()>
+    {
+        set_handle_nonblocking(&self.handle, nonblocking)
+    }
+}
+
+impl PipeWriter
+{
+    /// Return the path to this named pipe
+    pub fn path(&self) -> &Path
+    {
+        &self.cleanup.path
+    }
+
+    /// Sets the write timeout. See `Pipe::set_write_timeout`.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()>
+    {
+        self.timeout = check_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Returns the write timeout of this writer
+    pub fn write_timeout(&self) -> Option<Duration>
+    {
+        self.timeout
+    }
+
+    /// Returns true. See `Pipe::is_write_vectored`.
+    pub fn is_write_vectored(&self) -> bool
+    {
+        true
+    }
+
+    /// Waits until the reader has consumed everything written to the pipe. See
+    /// `Pipe::drain`.
+    pub fn drain(&self, timeout: Option<Duration>) -> Result<()>
+    {
+        drain_handle(self.handle.raw().ok_or(Errno::EBADF)?, timeout).map_err(|e| e.with_path(self.path()))
+    }
+
+    /// Moves this writer into or out of non-blocking mode. See 
+    /// `Pipe::set_nonblocking`.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()>
+    {
+        set_handle_nonblocking(&self.handle, nonblocking)
+    }
+}
+
+impl std::io::Read for PipeReader
+{
+    fn read(&mut self, bytes: &mut [u8]) -> std::io::Result<usize> 
+    {
+        let handle = self.handle.raw().ok_or(Errno::EBADF)?;
+        wait_for(handle, PollFlags::POLLIN, self.timeout)?;
+        unistd::read(handle, bytes).map_err(std::io::Error::from)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> 
+    {
+        let handle = self.handle.raw().ok_or(Errno::EBADF)?;
+        wait_for(handle, PollFlags::POLLIN, self.timeout)?;
+        uio::readv(handle, bufs).map_err(std::io::Error::from)
+    }
+}
+
+impl std::io::Write for PipeWriter
+{
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> 
+    {
+        let handle = self.handle.raw().ok_or(Errno::EBADF)?;
+        write_with_timeout(handle, bytes, self.timeout)
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> 
+    {
+        let handle = self.handle.raw().ok_or(Errno::EBADF)?;
+        writev_with_timeout(handle, bufs, self.timeout)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> 
+    {
+        // Writes go straight to the kernel, so there's nothing to flush
+        Ok(())
+    }
+}
+
+/// Returns the handle type matching the access mode of a descriptor
+fn handle_type_of(fd: RawFd) -> (OFlag, HandleType)
+{
+    let flags = fcntl::fcntl(fd, fcntl::FcntlArg::F_GETFL)
+        .map(OFlag::from_bits_truncate)
+        .unwrap_or(OFlag::O_RDWR);
+    let handle_type = match flags & OFlag::O_ACCMODE
+    {
+        OFlag::O_RDONLY => HandleType::Read,
+        OFlag::O_WRONLY => HandleType::Write,
+        _ => HandleType::Unknown
+    };
+    (flags, handle_type)
+}
+
+/// Takes ownership of the descriptor of the handle opened alongside the pipe.
+/// Any other handle is closed. If this pipe is a clone, the descriptor is 
+/// duplicated. Fails with `EBADF` if the handle isn't open.
+impl std::convert::TryFrom<Pipe> for OwnedFd
+{
+    type Error = Error;
+
+    fn try_from(mut pipe: Pipe) -> Result<OwnedFd>
+    {
+        std::mem::replace(&mut pipe.handle1, Handle::Weak(Weak::new(), HandleType::Unknown))
+            .into_owned()
+            .map_err(Error::from)
+    }
+}
+
+/// Creates a pipe from a descriptor. The descriptor is used for both reading
+/// and writing, subject to the access mode it was opened with. The pipe has 
+/// an empty path and is never deleted.
+impl From<OwnedFd> for Pipe
+{
+    fn from(fd: OwnedFd) -> Pipe
+    {
+        let (flags, handle_type) = handle_type_of(fd.as_raw_fd());
+        Pipe
+        {
+            handle1: Handle::Arc(Arc::new(fd), handle_type),
+            handle2: None,
+            path: PathBuf::new(),
+            is_slave: false,
+            delete: OnCleanup::NoDelete,
+            flags,
+            strict: false,
+            read_timeout: None,
+            write_timeout: None,
+            #[cfg(any(target_os="linux", target_os="android"))]
+            pending_capacity: None
+        }
+    }
+}
+
+/// See `From<OwnedFd> for Pipe`
+impl FromRawFd for Pipe
+{
+    unsafe fn from_raw_fd(fd: RawFd) -> Pipe
+    {
+        Pipe::from(OwnedFd::from_raw_fd(fd))
+    }
+}
+
+macro_rules! impl_fd_traits
+{
+    ($half:ident) => 
+    {
+        impl AsRawFd for $half
+        {
+            fn as_raw_fd(&self) -> RawFd
+            {
+                // The handle is always owned, so this can't fail
+                self.as_fd().as_raw_fd()
+            }
+        }
+
+        impl AsFd for $half
+        {
+            fn as_fd(&self) -> BorrowedFd<'_>
+            {
+                self.handle.borrow().expect("Split pipe handles are always owned")
+            }
+        }
+
+        /// The descriptor is owned by this half, so it's never duplicated.
+        impl From<$half> for OwnedFd
+        {
+            fn from(half: $half) -> OwnedFd
+            {
+                half.handle.into_owned().expect("Split pipe handles are always owned")
+            }
+        }
+
+        impl IntoRawFd for $half
+        {
+            fn into_raw_fd(self) -> RawFd
+            {
+                OwnedFd::from(self).into_raw_fd()
+            }
+        }
+
+        /// Creates a pipe half from a descriptor. It has an empty path and is
+        /// never deleted.
+        impl From<OwnedFd> for $half
+        {
+            fn from(fd: OwnedFd) -> $half
+            {
+                let handle_type = handle_type_of(fd.as_raw_fd()).1;
+                $half
+                {
+                    handle: Handle::Arc(Arc::new(fd), handle_type),
+                    cleanup: Arc::new(Cleanup { path: PathBuf::new(), delete: OnCleanup::NoDelete }),
+                    timeout: None
+                }
+            }
+        }
+
+        impl FromRawFd for $half
+        {
+            unsafe fn from_raw_fd(fd: RawFd) -> $half
+            {
+                $half::from(OwnedFd::from_raw_fd(fd))
+            }
+        }
+    }
+}
+
+impl_fd_traits!(PipeReader);
+impl_fd_traits!(PipeWriter);
+
+macro_rules! impl_mio_source
+{
+    ($half:ident) => 
+    {
+        /// Registering puts the handle in non-blocking mode, as `mio` requires. 
+        /// Reads and writes then fail with `ErrorKind::WouldBlock` until the 
+        /// handle is ready.
+        #[cfg(feature="mio")]
+        impl mio::event::Source for $half
+        {
+            fn register(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> std::io::Result<()>
+            {
+                self.set_nonblocking(true)?;
+                mio::unix::SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+            }
+
+            fn reregister(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> std::io::Result<()>
+            {
+                mio::unix::SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+            }
+
+            fn deregister(&mut self, registry: &mio::Registry) -> std::io::Result<()>
+            {
+                mio::unix::SourceFd(&self.as_raw_fd()).deregister(registry)
+            }
+        }
+    }
+}
+
+impl_mio_source!(PipeReader);
+impl_mio_source!(PipeWriter);
+
+/// Deletes the pipe file (if requested) once both halves of a split pipe have
+/// been dropped.
+#[derive(Debug)]
+struct Cleanup
+{
+    path: PathBuf,
+    delete: OnCleanup
+}
+
+impl Drop for Cleanup
+{
+    fn drop(&mut self) 
+    {
+        if let OnCleanup::Delete = self.delete
+        {
+            // The file may already have been removed by someone else, and
+            // there's no way to report an error from here anyway
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+mod ioctl
+{
+    nix::ioctl_read_bad!(fionread, nix::libc::FIONREAD, nix::libc::c_int);
+}
+
+/// Returns the number of bytes buffered in a pipe
+fn bytes_available(handle: RawFd) -> std::io::Result<usize>
+{
+    let mut available = 0;
+    // Safe because FIONREAD only writes a c_int to the given pointer
+    unsafe { ioctl::fionread(handle, &mut available) }?;
+    Ok(available as usize)
+}
+
+/// Waits until a pipe's kernel buffer is empty
+fn drain_handle(handle: RawFd, timeout: Option<Duration>) -> Result<()>
+{
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    while bytes_available(handle)? > 0
+    {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Err(Errno::ETIMEDOUT)?;
+        }
+        std::thread::sleep(RETRY_INTERVAL);
+    }
+    Ok(())
+}
+
+/// Sets or clears `O_NONBLOCK` on a handle. Handles that aren't open are 
+/// skipped.
+fn set_handle_nonblocking(handle: &Handle, nonblocking: bool) -> Result<()>
+{
+    if let Some(fd) = handle.raw()
+    {
+        let mut flags = OFlag::from_bits_truncate(fcntl::fcntl(fd, fcntl::FcntlArg::F_GETFL)?);
+        flags.set(OFlag::O_NONBLOCK, nonblocking);
+        fcntl::fcntl(fd, fcntl::FcntlArg::F_SETFL(flags))?;
+    }
+    Ok(())
+}
+
+/// Closes a handle if this is the last reference to it, reporting any error
+fn close_handle(handle: Handle) -> Result<()>
+{
+    match handle
+    {
+        Handle::Arc(arc, _) => match Arc::try_unwrap(arc)
+        {
+            Ok(fd) => unistd::close(fd.into_raw_fd()).map_err(Error::from),
+            // A clone is mid-read or mid-write on another thread and holds a 
+            // strong reference, so the handle is closed when it's done
+            Err(_) => Ok(())
+        },
+        Handle::Weak(..) => Ok(())
+    }
+}
+
+/// How often `wait_for_reader` and `PipeSet::wait` retry opening the pipe, 
+/// and how often `drain` checks whether the pipe is empty
+pub(crate) const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Rejects zero-length timeouts, mirroring `TcpStream::set_read_timeout`
+fn check_timeout(timeout: Option<Duration>) -> std::io::Result<Option<Duration>>
+{
+    if timeout == Some(Duration::from_secs(0))
+    {
+        Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "cannot set a 0 duration timeout"))
+    }
+    else
+    {
+        Ok(timeout)
+    }
+}
+
+/// Waits until the given events are ready on a handle. Returns immediately if 
+/// there is no timeout, and fails with `ErrorKind::TimedOut` if the timeout 
+/// elapses first. Hangups and errors are reported as ready so that the 
+/// following read or write can surface them.
+pub(crate) fn wait_for(handle: RawFd, events: PollFlags, timeout: Option<Duration>) -> std::io::Result<()>
+{
+    match timeout
+    {
+        Some(_) => poll_for(handle, events, timeout),
+        None => Ok(())
+    }
+}
+
+/// Writes to a handle, waiting up to the timeout for it to become writable.
+/// A writable pipe has room for at least `PIPE_BUF` bytes, so when there's a
+/// timeout the write is capped at that to keep it from blocking afterwards.
+fn write_with_timeout(handle: RawFd, bytes: &[u8], timeout: Option<Duration>) -> std::io::Result<usize>
+{
+    wait_for(handle, PollFlags::POLLOUT, timeout)?;
+    unistd::write(handle, &bytes[..capped_write_len(bytes.len(), timeout)]).map_err(std::io::Error::from)
+}
+
+/// Caps the length of a write at `PIPE_BUF` bytes if there's a timeout. See
+/// `write_with_timeout`.
+fn capped_write_len(len: usize, timeout: Option<Duration>) -> usize
+{
+    match timeout
+    {
+        Some(_) => len.min(crate::PIPE_BUF),
+        None => len
+    }
+}
+
+/// Vectored version of `write_with_timeout`, capping the total length of the
+/// buffers at `PIPE_BUF` when there's a timeout
+fn writev_with_timeout(handle: RawFd, bufs: &[std::io::IoSlice<'_>], timeout: Option<Duration>) -> std::io::Result<usize>
+{
+    wait_for(handle, PollFlags::POLLOUT, timeout)?;
+    if timeout.is_none()
+    {
+        return uio::writev(handle, bufs).map_err(std::io::Error::from);
+    }
+    let mut capped = Vec::with_capacity(bufs.len());
+    let mut remaining = crate::PIPE_BUF;
+    for buf in bufs
+    {
+        if remaining == 0
+        {
+            break;
+        }
+        let len = buf.len().min(remaining);
+        capped.push(std::io::IoSlice::new(&buf[..len]));
+        remaining -= len;
+    }
+    uio::writev(handle, &capped).map_err(std::io::Error::from)
+}
+
+/// Waits until the given events are ready on a handle. Waits indefinitely if
+/// the timeout is `None`.
+fn poll_for(handle: RawFd, events: PollFlags, timeout: Option<Duration>) -> std::io::Result<()>
+{
+    match poll_until(&mut [PollFd::new(handle, events)], timeout)?
+    {
+        0 => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out waiting on pipe")),
+        _ => Ok(())
+    }
+}
+
+/// Polls the descriptors until at least one is ready or the timeout elapses,
+/// retrying if interrupted. Returns the number of ready descriptors, which is
+/// 0 if the timeout elapsed. Waits indefinitely if the timeout is `None`.
+pub(crate) fn poll_until(fds: &mut [PollFd], timeout: Option<Duration>) -> nix::Result<usize>
+{
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop
+    {
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        // Round up so sub-millisecond timeouts don't become a busy loop
+        let millis = remaining.map_or(-1, |remaining| remaining.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32);
+        match poll(fds, millis)
+        {
+            Ok(0) if remaining.is_some_and(|remaining| remaining.is_zero()) => return Ok(0),
+            Ok(0) | Err(Errno::EINTR) => continue,
+            Ok(ready) => return Ok(ready as usize),
+            Err(err) => return Err(err)
         }
     }
 }
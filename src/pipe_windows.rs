@@ -293,6 +293,40 @@ impl std::io::Read for Pipe
     }
 }
 
+/// Returns the number of bytes that can be read without blocking, using 
+/// `PeekNamedPipe`. Returns 0 if the pipe hasn't been connected yet.
+impl crate::Available for Pipe
+{
+    fn available(&mut self) -> io::Result<usize>
+    {
+        match self.handle.as_ref().and_then(Handle::raw)
+        {
+            Some(raw) => 
+            {
+                let mut available: DWORD = 0;
+                let ok = unsafe 
+                {
+                    PeekNamedPipe(raw, 
+                                  std::ptr::null_mut(), 
+                                  0, 
+                                  std::ptr::null_mut(), 
+                                  &mut available, 
+                                  std::ptr::null_mut())
+                };
+                if ok != 0
+                {
+                    Ok(available as usize)
+                }
+                else
+                {
+                    Err(io::Error::last_os_error())
+                }
+            },
+            None => Ok(0)
+        }
+    }
+}
+
 impl Read for Handle 
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> 
@@ -10,9 +10,9 @@ fn test_pipe() -> crate::Result<()>
         let mut written = 0;
         for i in 1..=max
         {
-            written += pipe.write(&format!("{}\n", i).as_bytes())?;
+            written += pipe.write(format!("{}\n", i).as_bytes())?;
         }
-        written += pipe.write(&['X' as u8])?;
+        written += pipe.write(b"X")?;
         Ok(written)
     }
     let mut pipe = Pipe::create()?;
@@ -20,7 +20,7 @@ fn test_pipe() -> crate::Result<()>
     let writer = Arc::new(Mutex::from(pipe.clone()));
     let thread_writer = writer.clone();
 
-    let thread = thread::spawn(move || write_nums(&mut thread_writer.lock().as_mut().unwrap(), 10));
+    let thread = thread::spawn(move || write_nums(thread_writer.lock().as_mut().unwrap(), 10));
 
     let result = read_until_x(&mut pipe).unwrap();
     print!("{}", result);
@@ -28,7 +28,7 @@ fn test_pipe() -> crate::Result<()>
     println!("Bytes sent through the pipe: {:?}", thread.join().unwrap());
 
     let thread_writer = writer.clone();
-    let thread = thread::spawn(move || write_nums(&mut thread_writer.lock().as_mut().unwrap(), 3));
+    let thread = thread::spawn(move || write_nums(thread_writer.lock().as_mut().unwrap(), 3));
 
     let result = read_until_x(&mut pipe).unwrap();
     print!("{}", result);
@@ -119,13 +119,880 @@ fn read_until_x(pipe: &mut Pipe) -> std::io::Result<String>
     {
         match pipe.read(&mut buf)
         {
-            Ok(_) if buf[0] != 'X' as u8 => container.push(buf[0] as char),
+            Ok(_) if buf[0] != b'X' => container.push(buf[0] as char),
             Ok(_) => { break Ok(container);  }
             Err(e) => { break Err(e); }
         }
     }
 }
 
+#[cfg(unix)]
+#[test]
+fn test_split() -> crate::Result<()>
+{
+    use std::io::{BufRead, BufReader};
+    let pipe = crate::Pipe::open(&std::env::temp_dir().join(format!("split_{}", std::process::id())), crate::OnCleanup::Delete)?;
+    let path = pipe.path().to_path_buf();
+    let (reader, mut writer) = pipe.split()?;
+    assert_eq!(reader.path(), path);
+    assert_eq!(writer.path(), path);
+
+    let thread = thread::spawn(move || -> std::io::Result<()>
+    {
+        for i in 1..=3
+        {
+            writeln!(&mut writer, "This is line #{}", i)?;
+        }
+        Ok(())
+    });
+    let mut lines = BufReader::new(reader).lines();
+    for i in 1..=3
+    {
+        assert_eq!(format!("This is line #{}", i), lines.next().unwrap()?);
+    }
+    thread.join().unwrap()?;
+
+    // Both halves are dropped, so the pipe is deleted
+    drop(lines);
+    assert!(!path.exists());
+
+    // Dropping the halves or the pipe doesn't panic if the file is already gone
+    let pipe = crate::Pipe::open(&path, crate::OnCleanup::Delete)?;
+    let halves = pipe.split()?;
+    std::fs::remove_file(&path)?;
+    drop(halves);
+    let pipe = crate::Pipe::open(&path, crate::OnCleanup::Delete)?;
+    std::fs::remove_file(&path)?;
+    drop(pipe);
+
+    // Splitting a strict pipe opens the read end without waiting for a writer
+    let pipe = crate::PipeBuilder::new().path(&path).strict(true).on_cleanup(crate::OnCleanup::Delete).open()?;
+    let (mut reader, mut writer) = pipe.split()?;
+    writer.write_all(b"X")?;
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"X");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_strict() -> crate::Result<()>
+{
+    use std::sync::mpsc;
+    let path = std::env::temp_dir().join(format!("strict_{}", std::process::id()));
+    let open = || crate::PipeBuilder::new().path(&path).strict(true).open();
+    let mut reader = open()?;
+    let writer = open()?;
+
+    // Opening the write end blocks until the reader has opened its end
+    let (tx, rx) = mpsc::channel();
+    let thread = thread::spawn(move || -> std::io::Result<std::io::ErrorKind>
+    {
+        let mut writer = writer;
+        writer.write_all(b"X")?;
+        rx.recv().unwrap();
+        let kind = writer.write(b"X").unwrap_err().kind();
+        writer.close()?;
+        Ok(kind)
+    });
+
+    // Read returns Ok(0) once the last writer disconnects
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"X");
+    reader.close()?;
+    tx.send(()).unwrap();
+    assert_eq!(std::io::ErrorKind::BrokenPipe, thread.join().unwrap()?);
+
+    let mut reader = open()?;
+    let mut writer = open()?;
+    let thread = thread::spawn(move || -> crate::Result<()>
+    {
+        writer.write_all(b"X")?;
+        writer.close()
+    });
+    let mut contents = vec![];
+    reader.read_to_end(&mut contents)?;
+    assert_eq!(contents, b"X");
+    thread.join().unwrap()?;
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_drop_closes() -> crate::Result<()>
+{
+    let path = std::env::temp_dir().join(format!("drop_closes_{}", std::process::id()));
+    let open = || crate::PipeBuilder::new().path(&path).strict(true).open();
+    let mut reader = open()?;
+    let mut writer = open()?;
+
+    // The reader only sees EOF if the writer's handle is closed on drop
+    let thread = thread::spawn(move || writer.write_all(b"X"));
+    let mut contents = vec![];
+    reader.read_to_end(&mut contents)?;
+    assert_eq!(contents, b"X");
+    thread.join().unwrap()?;
+
+    // Closing a pipe closes both of its handles
+    let mut pipe = Pipe::open(&path, crate::OnCleanup::Delete)?;
+    pipe.write_all(b"X")?;
+    pipe.read_exact(&mut [0; 1])?;
+    pipe.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_timeout() -> crate::Result<()>
+{
+    use std::time::{Duration, Instant};
+    let mut pipe = Pipe::create()?;
+    assert_eq!(pipe.set_read_timeout(Some(Duration::from_secs(0))).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    pipe.set_read_timeout(Some(Duration::from_millis(50)))?;
+    assert_eq!(pipe.read_timeout(), Some(Duration::from_millis(50)));
+
+    let start = Instant::now();
+    let mut buf = [0; 1];
+    assert_eq!(pipe.read(&mut buf).unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    // Data that's already available is read without waiting
+    pipe.write_all(b"X")?;
+    pipe.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"X");
+
+    // A write larger than the pipe buffer gives up once the pipe fills, 
+    // rather than blocking in write(2) for a reader that never comes
+    let (_reader, mut writer) = pipe.split()?;
+    writer.set_write_timeout(Some(Duration::from_millis(100)))?;
+    let big = vec![0; 1 << 20];
+    assert!(writer.write(&big)? <= crate::PIPE_BUF);
+    let slices = [std::io::IoSlice::new(&big), std::io::IoSlice::new(&big)];
+    assert!(writer.write_vectored(&slices)? <= crate::PIPE_BUF);
+    let start = Instant::now();
+    assert_eq!(writer.write_all(&big).unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // Strict pipes don't block opening a handle while there's no peer
+    let path = std::env::temp_dir().join(format!("timeout_{}", std::process::id()));
+    let mut strict = crate::PipeBuilder::new().path(&path).strict(true).on_cleanup(crate::OnCleanup::Delete).open()?;
+    strict.set_read_timeout(Some(Duration::from_millis(100)))?;
+    strict.set_write_timeout(Some(Duration::from_millis(100)))?;
+    let start = Instant::now();
+    assert_eq!(strict.write(b"X").unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    assert_eq!(strict.read(&mut buf).unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    Ok(())
+}
+
+#[cfg(all(unix, feature="tokio"))]
+#[test]
+fn test_async() -> crate::Result<()>
+{
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_io().build()?;
+    runtime.block_on(async
+    {
+        let mut pipe = crate::AsyncPipe::create()?;
+        for i in 1..=3
+        {
+            pipe.write_all(format!("This is line #{}\n", i).as_bytes()).await?;
+        }
+        let mut lines = BufReader::new(pipe).lines();
+        for i in 1..=3
+        {
+            assert_eq!(Some(format!("This is line #{}", i)), lines.next_line().await?);
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn test_message() -> crate::Result<()>
+{
+    use crate::{MessagePipe, Error};
+    let mut pipe = MessagePipe::with_max_size(Pipe::create()?, 16);
+    let mut writer = MessagePipe::new(pipe.get_ref().clone());
+    let thread = thread::spawn(move || -> crate::Result<()>
+    {
+        writer.send(b"first")?;
+        writer.send(b"")?;
+        writer.send(b"a somewhat longer message")?;
+        writer.send(b"last")
+    });
+    assert_eq!(pipe.recv()?, b"first");
+    assert_eq!(pipe.recv()?, b"");
+    assert!(matches!(pipe.recv(), Err(Error::MessageTooLarge(25, 16))));
+    thread.join().unwrap()?;
+    assert!(matches!(pipe.send(&[0; 17]), Err(Error::MessageTooLarge(17, 16))));
+
+    // Truncated frames are reported rather than returned as short messages
+    let mut truncated = MessagePipe::new(std::io::Cursor::new(vec![0, 0, 0, 5, b'a', b'b']));
+    assert!(matches!(truncated.recv(), Err(Error::TruncatedMessage(6, 9))));
+    let mut truncated = MessagePipe::new(std::io::Cursor::new(vec![0, 0]));
+    assert!(matches!(truncated.recv(), Err(Error::TruncatedMessage(2, 4))));
+    let mut empty = MessagePipe::new(std::io::Cursor::new(vec![]));
+    assert!(matches!(empty.recv(), Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof));
+
+    // A receive that times out partway through a message can be resumed
+    #[cfg(unix)]
+    {
+        let mut pipe = MessagePipe::new(Pipe::create()?);
+        pipe.get_mut().set_read_timeout(Some(std::time::Duration::from_millis(20)))?;
+        let frame = crate::message::encode_frame(b"Hello");
+        for part in [&frame[..2], &frame[2..6]]
+        {
+            pipe.get_mut().write_all(part)?;
+            assert_eq!(pipe.recv().unwrap_err().kind(), crate::ErrorKind::TimedOut);
+        }
+        pipe.get_mut().write_all(&frame[6..])?;
+        assert_eq!(pipe.recv()?, b"Hello");
+    }
+    Ok(())
+}
+
+#[cfg(feature="serde")]
+#[test]
+fn test_typed() -> crate::Result<()>
+{
+    use crate::{TypedSender, TypedReceiver, Encoding, JsonLines, Binary};
+    use serde::{Serialize, Deserialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading
+    {
+        sensor: String,
+        value: f64
+    }
+
+    fn send_and_receive<E: Encoding + 'static>() -> crate::Result<()>
+    {
+        let pipe = Pipe::create()?;
+        let mut sender = TypedSender::<Reading, E>::new(pipe.clone());
+        let mut receiver = TypedReceiver::<Reading, E>::new(pipe);
+        let thread = thread::spawn(move || -> crate::Result<()>
+        {
+            for i in 0..3
+            {
+                sender.send(&Reading { sensor: format!("sensor\n{}", i), value: i as f64 / 2.0 })?;
+            }
+            Ok(())
+        });
+        for i in 0..3
+        {
+            assert_eq!(receiver.recv()?, Reading { sensor: format!("sensor\n{}", i), value: i as f64 / 2.0 });
+        }
+        thread.join().unwrap()
+    }
+    send_and_receive::<JsonLines>()?;
+    send_and_receive::<Binary>()?;
+
+    // Iteration ends at EOF
+    let encoded = [JsonLines::encode(&1u8, 8)?, JsonLines::encode(&2u8, 8)?].concat();
+    let receiver = TypedReceiver::<u8, JsonLines, _>::new(std::io::Cursor::new(encoded));
+    assert_eq!(receiver.collect::<crate::Result<Vec<_>>>()?, vec![1, 2]);
+
+    // Values larger than the maximum are rejected by both encodings
+    let long = "x".repeat(100);
+    for result in [JsonLines::encode(&long, 64), Binary::encode(&long, 64)]
+    {
+        assert!(matches!(result, Err(crate::Error::MessageTooLarge(_, 64))));
+    }
+    let mut sender = TypedSender::<String, Binary, _>::with_max_size(Vec::new(), 64);
+    assert!(matches!(sender.send(&long), Err(crate::Error::MessageTooLarge(_, 64))));
+
+    // An overlong line is skipped without reading all of it into memory, and
+    // the next value can still be received
+    let encoded = [JsonLines::encode(&long, 1024)?, JsonLines::encode(&"short", 1024)?].concat();
+    let mut receiver = TypedReceiver::<String, JsonLines, _>::with_max_size(std::io::Cursor::new(encoded), 64);
+    assert!(matches!(receiver.recv(), Err(crate::Error::MessageTooLarge(65, 64))));
+    assert_eq!(receiver.recv()?, "short");
+
+    let encoded = Binary::encode(&long, 1024)?;
+    let mut receiver = TypedReceiver::<String, Binary, _>::with_max_size(std::io::Cursor::new(encoded), 64);
+    assert!(matches!(receiver.recv(), Err(crate::Error::MessageTooLarge(_, 64))));
+    Ok(())
+}
+
+#[cfg(feature="channels")]
+#[test]
+fn test_channels() -> crate::Result<()>
+{
+    let pipe = Pipe::create()?;
+    let (tx, writer) = pipe.clone().sender_blocking();
+    let (rx, _) = pipe.receiver_blocking();
+    tx.send(b"Hello, ".to_vec()).unwrap();
+    tx.send(b"world".to_vec()).unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < 12
+    {
+        received.extend(rx.recv().unwrap()?);
+    }
+    assert_eq!(received, b"Hello, world");
+
+    // The writer thread shuts down cleanly when the sender is dropped
+    drop(tx);
+    writer.join().unwrap()?;
+    Ok(())
+}
+
+#[cfg(feature="tokio_channels")]
+#[test]
+fn test_tokio_channels() -> crate::Result<()>
+{
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    runtime.block_on(async
+    {
+        let pipe = Pipe::create()?;
+        let (tx, writer) = pipe.clone().sender_async();
+        let (mut rx, reader) = pipe.receiver_async();
+        tx.send(b"Hello, ".to_vec()).unwrap();
+        tx.send(b"world".to_vec()).unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < 12
+        {
+            received.extend(rx.recv().await.unwrap()?);
+        }
+        assert_eq!(received, b"Hello, world");
+
+        // Both tasks shut down cleanly when the other end is dropped
+        drop(tx);
+        writer.await.unwrap()?;
+        drop(rx);
+        reader.await.unwrap();
+        Ok::<_, crate::Error>(())
+    })?;
+
+    // Tasks still waiting on a pipe don't stop the runtime from shutting down
+    runtime.block_on(async
+    {
+        let (_rx, _) = Pipe::create()?.receiver_async();
+        // A strict pipe without a reader can't be opened for writing yet
+        let strict = crate::PipeBuilder::new().strict(true).on_cleanup(crate::OnCleanup::Delete).open()?;
+        let (_tx, _) = strict.sender_async();
+        tokio::task::yield_now().await;
+        Ok::<_, crate::Error>(())
+    })?;
+    drop(runtime);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_error_kinds() -> crate::Result<()>
+{
+    use crate::{Error, ErrorKind, PipeBuilder};
+    use nix::errno::Errno;
+
+    let path = std::env::temp_dir().join(format!("error_kinds_{}", std::process::id()));
+    let err = PipeBuilder::new().path(&path).create(false).open().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert_eq!(err.path(), Some(path.as_path()));
+    assert_eq!(err.raw_os_error(), Some(Errno::ENOENT as i32));
+    let err = std::io::Error::from(err);
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert_eq!(err.raw_os_error(), Some(Errno::ENOENT as i32));
+
+    std::fs::write(&path, "")?;
+    let err = Pipe::open(&path, crate::OnCleanup::NoDelete).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotAFifo);
+    assert_eq!(err.path(), Some(path.as_path()));
+    assert_eq!(std::io::Error::from(err).kind(), std::io::ErrorKind::InvalidInput);
+    std::fs::remove_file(&path)?;
+
+    // Errors std retry loops depend on keep their kind and code
+    for (errno, kind, io_kind) in [(Errno::EINTR, ErrorKind::Interrupted, std::io::ErrorKind::Interrupted),
+                                   (Errno::EAGAIN, ErrorKind::WouldBlock, std::io::ErrorKind::WouldBlock),
+                                   (Errno::EACCES, ErrorKind::PermissionDenied, std::io::ErrorKind::PermissionDenied),
+                                   (Errno::EPIPE, ErrorKind::Disconnected, std::io::ErrorKind::BrokenPipe)]
+    {
+        let err = Error::from(errno);
+        assert_eq!(err.kind(), kind);
+        let err = std::io::Error::from(err);
+        assert_eq!(err.kind(), io_kind);
+        assert_eq!(err.raw_os_error(), Some(errno as i32));
+    }
+
+    // Where the kinds differ, the code is still reachable through the source
+    let err = std::io::Error::from(Error::from(Errno::ENXIO));
+    assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+    let source = std::error::Error::source(&err).unwrap();
+    assert_eq!(source.downcast_ref::<std::io::Error>().unwrap().raw_os_error(), Some(Errno::ENXIO as i32));
+
+    // Opening a write end without blocking fails if there's no reader
+    let mut pipe = PipeBuilder::new().path(&path).strict(true).nonblocking(true).on_cleanup(crate::OnCleanup::Delete).open()?;
+    assert_eq!(pipe.write(b"X").unwrap_err().kind(), std::io::ErrorKind::BrokenPipe);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fd() -> crate::Result<()>
+{
+    use crate::{PipeReader, PipeWriter};
+    use std::convert::TryFrom;
+    use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+
+    let pipe = Pipe::create()?;
+    let path = pipe.path().to_path_buf();
+    let fd = pipe.try_as_fd()?.as_raw_fd();
+
+    // Clones and unopened strict pipes don't own a descriptor to borrow
+    assert_eq!(pipe.clone().try_as_fd().unwrap_err().raw_os_error(), Some(nix::libc::EBADF));
+    let strict = crate::PipeBuilder::new().path(&path).strict(true).open()?;
+    assert!(strict.try_as_fd().is_err());
+    drop(strict);
+
+    // Descriptors survive a round trip through OwnedFd
+    let owned = OwnedFd::try_from(pipe)?;
+    assert_eq!(owned.as_raw_fd(), fd);
+    let mut pipe = Pipe::from(owned);
+    pipe.write_all(b"X")?;
+    let mut buf = [0; 1];
+    pipe.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"X");
+    drop(pipe);
+    std::fs::remove_file(&path)?;
+
+    let (reader, writer) = Pipe::create()?.split()?;
+    let mut reader = unsafe { PipeReader::from_raw_fd(reader.into_raw_fd()) };
+    let mut writer = unsafe { PipeWriter::from_raw_fd(writer.into_raw_fd()) };
+    writer.write_all(b"Y")?;
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"Y");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_try_clone() -> crate::Result<()>
+{
+    let path = std::env::temp_dir().join(format!("try_clone_{}", std::process::id()));
+    let mut pipe = Pipe::open(&path, crate::OnCleanup::Delete)?;
+    pipe.write_all(b"X")?;
+    pipe.read_exact(&mut [0; 1])?;
+    let mut weak = pipe.clone();
+    let mut owned = pipe.try_clone()?;
+
+    // A read which is already in progress on a clone outlives the original
+    let mut reading = pipe.clone();
+    let thread = thread::spawn(move || -> std::io::Result<[u8; 1]>
+    {
+        let mut buf = [0; 1];
+        reading.read_exact(&mut buf)?;
+        Ok(buf)
+    });
+    thread::sleep(std::time::Duration::from_millis(50));
+    drop(pipe);
+    owned.write_all(b"Y")?;
+    assert_eq!(&thread.join().unwrap()?, b"Y");
+
+    // Only the duplicate keeps working once the original is gone
+    assert!(weak.write(b"X").is_err());
+    owned.write_all(b"X")?;
+    let mut buf = [0; 1];
+    owned.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"X");
+    assert!(!path.exists());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_wait_for_peer() -> crate::Result<()>
+{
+    use std::time::Duration;
+    let path = std::env::temp_dir().join(format!("wait_for_peer_{}", std::process::id()));
+    let open = || crate::PipeBuilder::new().path(&path).strict(true).open();
+    let mut writer = open()?;
+    let mut reader = open()?;
+    assert_eq!(writer.wait_for_reader(Some(Duration::from_millis(20))).unwrap_err().kind(), crate::ErrorKind::TimedOut);
+    assert_eq!(reader.wait_for_writer(Some(Duration::from_millis(20))).unwrap_err().kind(), crate::ErrorKind::TimedOut);
+
+    let thread = thread::spawn(move || -> crate::Result<Pipe>
+    {
+        thread::sleep(Duration::from_millis(20));
+        reader.wait_for_writer(Some(Duration::from_secs(5)))?;
+        Ok(reader)
+    });
+    writer.wait_for_reader(Some(Duration::from_secs(5)))?;
+    writer.write_all(b"X")?;
+    let mut reader = thread.join().unwrap()?;
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"X");
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_nonblocking() -> crate::Result<()>
+{
+    let mut pipe = Pipe::create()?;
+    pipe.set_nonblocking(true)?;
+    let mut buf = [0; 1];
+    assert_eq!(pipe.read(&mut buf).unwrap_err().kind(), std::io::ErrorKind::WouldBlock);
+    pipe.write_all(b"X")?;
+    pipe.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"X");
+    assert_eq!(pipe.read(&mut buf).unwrap_err().kind(), std::io::ErrorKind::WouldBlock);
+    pipe.set_nonblocking(false)?;
+
+    let (mut reader, _writer) = pipe.split()?;
+    reader.set_nonblocking(true)?;
+    assert_eq!(reader.read(&mut buf).unwrap_err().kind(), std::io::ErrorKind::WouldBlock);
+    Ok(())
+}
+
+#[cfg(all(unix, feature="mio"))]
+#[test]
+fn test_mio() -> crate::Result<()>
+{
+    use mio::{Events, Interest, Poll, Token};
+    use std::time::Duration;
+
+    let (mut reader, mut writer) = Pipe::create()?.split()?;
+    let mut poll = Poll::new()?;
+    let mut events = Events::with_capacity(4);
+    poll.registry().register(&mut reader, Token(0), Interest::READABLE)?;
+    poll.registry().register(&mut writer, Token(1), Interest::WRITABLE)?;
+
+    // Registering makes reads non-blocking
+    let mut buf = [0; 1];
+    assert_eq!(reader.read(&mut buf).unwrap_err().kind(), std::io::ErrorKind::WouldBlock);
+
+    poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+    assert!(events.iter().any(|event| event.token() == Token(1) && event.is_writable()));
+    writer.write_all(b"X")?;
+    let mut readable = false;
+    while !readable
+    {
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+        readable = events.iter().any(|event| event.token() == Token(0) && event.is_readable());
+    }
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"X");
+    poll.registry().deregister(&mut reader)?;
+    poll.registry().deregister(&mut writer)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pipe_set() -> crate::Result<()>
+{
+    use crate::{Direction, PipeSet};
+    use std::time::Duration;
+
+    let mut set = PipeSet::new();
+    for key in 0..3
+    {
+        assert!(set.insert(key, Pipe::create()?, Direction::Read).is_none());
+    }
+    assert_eq!(set.len(), 3);
+    assert!(set.wait(Some(Duration::from_millis(20)))?.is_empty());
+
+    set.get_mut(&1).unwrap().write_all(b"X")?;
+    let ready = set.wait(Some(Duration::from_secs(5)))?;
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].0, 1);
+    assert!(ready[0].1.readable && !ready[0].1.hangup);
+
+    let mut pipe = set.remove(&1).unwrap();
+    pipe.read_exact(&mut [0; 1])?;
+    assert_eq!(set.len(), 2);
+
+    // A strict writer without a reader isn't ready, and doesn't stall the set
+    let path = std::env::temp_dir().join(format!("pipe_set_{}", std::process::id()));
+    let strict = crate::PipeBuilder::new().path(&path).strict(true).on_cleanup(crate::OnCleanup::Delete).open()?;
+    let mut set = PipeSet::new();
+    set.insert("writer", strict, Direction::Write);
+    assert!(set.wait(Some(Duration::from_millis(100)))?.is_empty());
+    let mut reader = crate::PipeBuilder::new().path(&path).strict(true).open()?;
+    reader.wait_for_writer(Some(Duration::from_millis(1))).unwrap_err();
+    let ready = set.wait(Some(Duration::from_secs(5)))?;
+    assert_eq!(ready.len(), 1);
+    assert!(ready[0].1.writable);
+    Ok(())
+}
+
+#[cfg(target_os="linux")]
+#[test]
+fn test_capacity() -> crate::Result<()>
+{
+    let mut pipe = crate::PipeBuilder::new().capacity(128 * 1024).on_cleanup(crate::OnCleanup::Delete).open()?;
+    assert_eq!(pipe.capacity()?, 128 * 1024);
+    pipe.set_capacity(4096)?;
+    assert_eq!(pipe.capacity()?, 4096);
+
+    // Strict pipes apply the capacity once their first handle is opened
+    let mut strict = crate::PipeBuilder::new().strict(true).capacity(8192).on_cleanup(crate::OnCleanup::Delete).open()?;
+    assert!(strict.capacity().is_err());
+    strict.wait_for_writer(Some(std::time::Duration::from_millis(1))).unwrap_err();
+    assert_eq!(strict.capacity()?, 8192);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_peek() -> crate::Result<()>
+{
+    let mut pipe = crate::PeekablePipe::new(Pipe::create()?);
+    assert_eq!(pipe.available()?, 0);
+    pipe.write_all(b"Hello")?;
+    assert_eq!(pipe.get_mut().available()?, 5);
+
+    let mut buf = [0; 3];
+    assert_eq!(pipe.peek(&mut buf)?, 3);
+    assert_eq!(&buf, b"Hel");
+    assert_eq!(pipe.available()?, 5);
+
+    let mut buf = [0; 5];
+    pipe.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"Hello");
+    assert_eq!(pipe.available()?, 0);
+
+    // Peeking more than is available returns what's there without blocking
+    pipe.write_all(b"abc")?;
+    let mut buf = [0; 10];
+    assert_eq!(pipe.peek(&mut buf)?, 3);
+    assert_eq!(&buf[..3], b"abc");
+    pipe.read_exact(&mut buf[..3])?;
+
+    // Peek a length header, then the whole frame once it has all arrived
+    let (reader, mut writer) = Pipe::create()?.split()?;
+    let mut pipe = crate::PeekablePipe::new(reader);
+    writer.write_all(&[5, b'H'])?;
+    let mut header = [0; 1];
+    assert_eq!(pipe.peek(&mut header)?, 1);
+    let mut frame = vec![0; 1 + header[0] as usize];
+    assert_eq!(pipe.peek(&mut frame)?, 2);
+    writer.write_all(b"ello")?;
+    assert!(pipe.available()? >= frame.len());
+    assert_eq!(pipe.peek(&mut frame)?, 6);
+    assert_eq!(&frame, b"\x05Hello");
+    let mut buf = [0; 6];
+    pipe.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"\x05Hello");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_drain() -> crate::Result<()>
+{
+    use std::time::Duration;
+    let (mut reader, mut writer) = Pipe::create()?.split()?;
+    writer.write_all(b"Hello")?;
+    writer.flush()?;
+    assert_eq!(writer.drain(Some(Duration::from_millis(20))).unwrap_err().kind(), crate::ErrorKind::TimedOut);
+
+    let thread = thread::spawn(move || -> std::io::Result<()>
+    {
+        thread::sleep(Duration::from_millis(20));
+        reader.read_exact(&mut [0; 5])
+    });
+    writer.drain(Some(Duration::from_secs(5)))?;
+    thread.join().unwrap()?;
+    Ok(())
+}
+
+#[cfg(target_os="linux")]
+#[test]
+fn test_splice() -> crate::Result<()>
+{
+    use std::io::{Seek, SeekFrom};
+    let path = std::env::temp_dir().join(format!("splice_{}", std::process::id()));
+    let mut file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+    file.write_all(b"Hello")?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut pipe = Pipe::create()?;
+    let mut copy = Pipe::create()?;
+    assert_eq!(pipe.splice_from(&file, 5)?, 5);
+    assert_eq!(pipe.tee_to(&mut copy, 5)?, 5);
+    // Safe because the buffer is a static literal, which is never modified
+    assert_eq!(unsafe { pipe.vmsplice(b" world") }?, 6);
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    assert_eq!(pipe.splice_to(&file, 11)?, 11);
+    assert_eq!(std::fs::read(&path)?, b"Hello world");
+    let mut buf = [0; 5];
+    copy.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"Hello");
+
+    // With a write timeout, splicing is capped like a write
+    file.set_len(1 << 20)?;
+    file.seek(SeekFrom::Start(0))?;
+    pipe.set_write_timeout(Some(std::time::Duration::from_millis(100)))?;
+    assert!(pipe.splice_from(&file, 1 << 20)? <= crate::PIPE_BUF);
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_vectored() -> crate::Result<()>
+{
+    use std::io::{IoSlice, IoSliceMut};
+    let mut pipe = Pipe::create()?;
+    assert!(pipe.is_write_vectored());
+    assert_eq!(pipe.write_vectored(&[IoSlice::new(b"head"), IoSlice::new(b"payload")])?, 11);
+
+    let (mut header, mut payload) = ([0; 4], [0; 7]);
+    assert_eq!(pipe.read_vectored(&mut [IoSliceMut::new(&mut header), IoSliceMut::new(&mut payload)])?, 11);
+    assert_eq!(&header, b"head");
+    assert_eq!(&payload, b"payload");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_write_atomic() -> crate::Result<()>
+{
+    use crate::{AtomicRecordReader, Error, MAX_ATOMIC_RECORD_SIZE};
+    let path = std::env::temp_dir().join(format!("write_atomic_{}", std::process::id()));
+    let open = || crate::PipeBuilder::new().path(&path).strict(true).open();
+    let mut reader = crate::PipeBuilder::new().path(&path).direction(crate::Direction::Read).nonblocking(true).open()?;
+    reader.set_nonblocking(false)?;
+    let mut reader = AtomicRecordReader::new(reader);
+    let record = vec![b'X'; MAX_ATOMIC_RECORD_SIZE];
+
+    // Open every writer up front so the reader can't see EOF early
+    let writers: Vec<_> = (0..4).map(|_| -> crate::Result<_>
+    {
+        let mut writer = open()?;
+        writer.wait_for_reader(None)?;
+        let record = record.clone();
+        Ok(thread::spawn(move || -> crate::Result<()>
+        {
+            assert!(matches!(writer.write_atomic(&vec![0; MAX_ATOMIC_RECORD_SIZE + 1]), Err(Error::MessageTooLarge(..))));
+            (0..16).try_for_each(|_| writer.write_atomic(&record))
+        }))
+    }).collect::<crate::Result<_>>()?;
+
+    // The reader sees EOF once every writer is done
+    let mut count = 0;
+    for received in &mut reader
+    {
+        assert_eq!(received?, record);
+        count += 1;
+    }
+    assert_eq!(count, 64);
+    for writer in writers
+    {
+        writer.join().unwrap()?;
+    }
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn test_delimited() -> crate::Result<()>
+{
+    use crate::{DelimitedPipe, Error, LinesPipe};
+    let mut pipe = DelimitedPipe::with_max_size(Pipe::create()?, b'\0', 8);
+    pipe.send(b"one")?;
+    pipe.send(b"")?;
+    assert!(matches!(pipe.send(b"t\0o"), Err(Error::Ipipe(_))));
+    assert!(matches!(pipe.send(b"too large"), Err(Error::MessageTooLarge(9, 8))));
+    assert_eq!(pipe.recv()?.unwrap(), b"one");
+    assert_eq!(pipe.recv()?.unwrap(), b"");
+
+    let mut pipe = LinesPipe::with_max_size(pipe.into_inner(), 8);
+    pipe.send("first")?;
+    pipe.get_mut().write_all(b"second\r\n\xFF\nthird\n")?;
+    assert_eq!(pipe.recv()?.unwrap(), "first");
+    assert_eq!(pipe.recv()?.unwrap(), "second");
+    assert!(matches!(pipe.recv(), Err(Error::InvalidUtf8)));
+    assert_eq!(pipe.next().unwrap()?, "third");
+    pipe.get_mut().write_all(b"much too long\n")?;
+    assert!(matches!(pipe.recv(), Err(Error::MessageTooLarge(_, 8))));
+
+    // The rest of a record that's too large is skipped, not received
+    let mut input = vec![b'a'; 4096];
+    input.extend_from_slice(b"TAIL\nnext\n");
+    let mut pipe = LinesPipe::with_max_size(&input[..], 100);
+    assert!(matches!(pipe.recv(), Err(Error::MessageTooLarge(_, 100))));
+    assert_eq!(pipe.recv()?.unwrap(), "next");
+    assert!(pipe.recv()?.is_none());
+
+    // A partial record is returned at EOF
+    let mut pipe = LinesPipe::new(&b"last\nunterminated"[..]);
+    assert_eq!(pipe.by_ref().collect::<crate::Result<Vec<_>>>()?, ["last", "unterminated"]);
+    Ok(())
+}
+
+#[cfg(all(unix, feature="codec"))]
+#[test]
+fn test_codec() -> crate::Result<()>
+{
+    use crate::{Error, LineCodec, MessageCodec};
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::{Decoder, Framed};
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_io().build()?;
+    runtime.block_on(async
+    {
+        let mut framed = Framed::new(crate::AsyncPipe::create()?, MessageCodec::with_max_size(16));
+        framed.send(b"first".to_vec()).await?;
+        framed.send(&b""[..]).await?;
+        assert!(matches!(framed.send(vec![0; 17]).await, Err(Error::MessageTooLarge(17, 16))));
+        assert_eq!(framed.next().await.unwrap()?, b"first");
+        assert_eq!(framed.next().await.unwrap()?, b"");
+
+        let mut framed = framed.map_codec(|_| LineCodec::with_max_size(8));
+        framed.send("line").await?;
+        assert!(matches!(framed.send("new\nline").await, Err(Error::Ipipe(_))));
+        assert_eq!(framed.next().await.unwrap()?, "line");
+
+        #[cfg(feature="serde")]
+        {
+            let mut framed = framed.map_codec(|_| crate::JsonCodec::<Vec<u32>>::new());
+            framed.send(vec![1, 2, 3]).await?;
+            assert_eq!(framed.next().await.unwrap()?, [1, 2, 3]);
+        }
+        crate::Result::Ok(())
+    })?;
+
+    // Partial frames at EOF
+    let mut buf = bytes::BytesMut::from(&b"\0\0\0\x05abc"[..]);
+    assert!(matches!(MessageCodec::new().decode_eof(&mut buf), Err(Error::TruncatedMessage(7, 9))));
+    let mut buf = bytes::BytesMut::from(&b"last\r\nunterminated"[..]);
+    let mut codec = LineCodec::new();
+    assert_eq!(codec.decode_eof(&mut buf)?.unwrap(), "last");
+    assert_eq!(codec.decode_eof(&mut buf)?.unwrap(), "unterminated");
+    assert!(codec.decode_eof(&mut buf)?.is_none());
+    let mut buf = bytes::BytesMut::from(&b"\xFF\n"[..]);
+    assert!(matches!(codec.decode(&mut buf), Err(Error::InvalidUtf8)));
+
+    // Decoding doesn't panic if the buffer is cleared after a partial line
+    let mut buf = bytes::BytesMut::from(&b"partial"[..]);
+    assert!(codec.decode(&mut buf)?.is_none());
+    buf.clear();
+    assert!(codec.decode(&mut buf)?.is_none());
+    buf.extend_from_slice(b"ok\n");
+    assert_eq!(codec.decode(&mut buf)?.unwrap(), "ok");
+
+    // After a line that's too long is rejected, the next line starts afresh
+    let mut codec = LineCodec::with_max_size(4);
+    let mut buf = bytes::BytesMut::from(&b"too"[..]);
+    assert!(codec.decode(&mut buf)?.is_none());
+    buf.extend_from_slice(b"long");
+    assert!(matches!(codec.decode(&mut buf), Err(Error::MessageTooLarge(7, 4))));
+    buf.clear();
+    buf.extend_from_slice(b"ok\n");
+    assert_eq!(codec.decode(&mut buf)?.unwrap(), "ok");
+    Ok(())
+}
+
 #[test]
 fn test_name()
 {
@@ -133,3 +1000,49 @@ fn test_name()
     assert_eq!(pipe.name().unwrap(), "test_name");
 }
 
+
+#[cfg(unix)]
+#[test]
+fn test_builder() -> crate::Result<()>
+{
+    use crate::{PipeBuilder, Direction};
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let dir = std::env::temp_dir().join(format!("ipipe_builder_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+
+    // A pipe that must already exist can't be opened before it's created
+    let missing = PipeBuilder::new().directory(&dir).name("builder").create(false).open();
+    assert!(missing.is_err());
+
+    let mut pipe = PipeBuilder::new()
+        .directory(&dir)
+        .name("builder")
+        .mode(0o600)
+        .on_cleanup(crate::OnCleanup::Delete)
+        .open()?;
+    let path = pipe.path().to_path_buf();
+    assert_eq!(path, dir.join("builder"));
+    let metadata = std::fs::metadata(&path)?;
+    assert!(metadata.file_type().is_fifo());
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+    // Opening write-only without a reader would block, so open non-blocking
+    let mut reader = PipeBuilder::new()
+        .path(&path)
+        .direction(Direction::Read)
+        .nonblocking(true)
+        .create(false)
+        .open()?;
+    assert!(reader.write(b"X").is_err());
+    pipe.write_all(b"X")?;
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"X");
+
+    drop(reader);
+    drop(pipe);
+    assert!(!path.exists());
+    std::fs::remove_dir(&dir)?;
+    Ok(())
+}
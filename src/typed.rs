@@ -0,0 +1,228 @@
+//! This module contains typed channels which send serde-serializable values 
+//! over a pipe.
+
+use crate::{Error, MessagePipe, Pipe, Result, DEFAULT_MAX_MESSAGE_SIZE};
+use crate::message::encode_frame;
+use serde::{Serialize, de::DeserializeOwned};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::marker::PhantomData;
+
+/// An encoding used to send values over a pipe. Implement this to use a custom
+/// wire format with `TypedSender` and `TypedReceiver`.
+pub trait Encoding
+{
+    /// Encodes a value into a complete frame, ready to be written to a pipe.
+    /// Fails with `Error::MessageTooLarge` if the encoded value, not counting
+    /// any framing, is larger than `max_size`.
+    fn encode<T: Serialize + ?Sized>(value: &T, max_size: usize) -> Result<Vec<u8>>;
+
+    /// Decodes the next value from a reader. Returns `None` if the reader is 
+    /// at EOF before any part of a value has been read. Fails with 
+    /// `Error::MessageTooLarge` if the encoded value is larger than 
+    /// `max_size`, without reading more than that into memory.
+    fn decode<T: DeserializeOwned, R: BufRead>(reader: &mut R, max_size: usize) -> Result<Option<T>>;
+}
+
+/// Encodes each value as a line of JSON
+#[derive(Debug, Clone, Copy)]
+pub struct JsonLines;
+
+/// Encodes each value with bincode, framed the same way as `MessagePipe`
+#[derive(Debug, Clone, Copy)]
+pub struct Binary;
+
+impl Encoding for JsonLines
+{
+    fn encode<T: Serialize + ?Sized>(value: &T, max_size: usize) -> Result<Vec<u8>>
+    {
+        // serde_json never emits raw newlines, so each value is a single line
+        let mut frame = serde_json::to_vec(value).map_err(|e| Error::Serialization(e.to_string()))?;
+        if frame.len() > max_size
+        {
+            return Err(Error::MessageTooLarge(frame.len(), max_size));
+        }
+        frame.push(b'\n');
+        Ok(frame)
+    }
+
+    fn decode<T: DeserializeOwned, R: BufRead>(reader: &mut R, max_size: usize) -> Result<Option<T>>
+    {
+        // Reading one byte past the limit is enough to tell the line is too long
+        let mut line = Vec::new();
+        if reader.take(max_size as u64 + 1).read_until(b'\n', &mut line)? == 0
+        {
+            return Ok(None);
+        }
+        if line.last() != Some(&b'\n')
+        {
+            if line.len() > max_size
+            {
+                // Skip the rest of the line so the next value can be decoded
+                skip_line(reader)?;
+                return Err(Error::MessageTooLarge(line.len(), max_size));
+            }
+            return Err(Error::TruncatedMessage(line.len(), line.len() + 1));
+        }
+        serde_json::from_slice(&line).map(Some).map_err(|e| Error::Serialization(e.to_string()))
+    }
+}
+
+impl Encoding for Binary
+{
+    fn encode<T: Serialize + ?Sized>(value: &T, max_size: usize) -> Result<Vec<u8>>
+    {
+        let message = bincode::serialize(value).map_err(|e| Error::Serialization(e.to_string()))?;
+        // The length prefix can't describe anything larger
+        let max_size = max_size.min(u32::MAX as usize);
+        if message.len() > max_size
+        {
+            return Err(Error::MessageTooLarge(message.len(), max_size));
+        }
+        Ok(encode_frame(&message))
+    }
+
+    fn decode<T: DeserializeOwned, R: BufRead>(reader: &mut R, max_size: usize) -> Result<Option<T>>
+    {
+        if reader.fill_buf()?.is_empty()
+        {
+            return Ok(None);
+        }
+        let message = MessagePipe::with_max_size(reader, max_size).recv()?;
+        bincode::deserialize(&message).map(Some).map_err(|e| Error::Serialization(e.to_string()))
+    }
+}
+
+/// Skips everything up to and including the next newline
+fn skip_line<R: BufRead>(reader: &mut R) -> Result<()>
+{
+    loop
+    {
+        let (len, found) = match reader.fill_buf()
+        {
+            Ok(buf) => match buf.iter().position(|&b| b == b'\n')
+            {
+                Some(pos) => (pos + 1, true),
+                None => (buf.len(), buf.is_empty())
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into())
+        };
+        reader.consume(len);
+        if found
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Sends values of type `T` over a pipe using the encoding `E`
+/// 
+/// ```
+/// use ipipe::{Pipe, TypedSender, TypedReceiver, Binary};
+/// let pipe = Pipe::create().unwrap();
+/// let mut sender = TypedSender::<(u32, String), Binary>::new(pipe.clone());
+/// let mut receiver = TypedReceiver::<(u32, String), Binary>::new(pipe);
+/// sender.send(&(1, "one".to_string())).unwrap();
+/// assert_eq!(receiver.recv().unwrap(), (1, "one".to_string()));
+/// ```
+#[derive(Debug)]
+pub struct TypedSender<T, E = JsonLines, P = Pipe>
+{
+    inner: P,
+    max_size: usize,
+    _marker: PhantomData<fn(&T, E)>
+}
+
+/// Receives values of type `T` from a pipe using the encoding `E`. Iterating
+/// over a receiver yields values until the pipe reaches EOF.
+#[derive(Debug)]
+pub struct TypedReceiver<T, E = JsonLines, P = Pipe>
+{
+    inner: BufReader<P>,
+    max_size: usize,
+    _marker: PhantomData<fn(E) -> T>
+}
+
+impl<T: Serialize, E: Encoding, P: Write> TypedSender<T, E, P>
+{
+    /// Wraps a pipe (or any other `Write` type), with a maximum encoded size
+    /// of `DEFAULT_MAX_MESSAGE_SIZE`
+    pub fn new(inner: P) -> Self
+    {
+        Self::with_max_size(inner, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Wraps a pipe with the given maximum encoded size per value. Sending a
+    /// larger value fails with `Error::MessageTooLarge`.
+    pub fn with_max_size(inner: P, max_size: usize) -> Self
+    {
+        TypedSender { inner, max_size, _marker: PhantomData }
+    }
+
+    /// Returns the maximum encoded size
+    pub fn max_size(&self) -> usize
+    {
+        self.max_size
+    }
+
+    /// Sends a value. Each value is written with a single call to `write_all`.
+    pub fn send(&mut self, value: &T) -> Result<()>
+    {
+        self.inner.write_all(&E::encode(value, self.max_size)?)?;
+        Ok(())
+    }
+
+    /// Unwraps the underlying pipe
+    pub fn into_inner(self) -> P
+    {
+        self.inner
+    }
+}
+
+impl<T: DeserializeOwned, E: Encoding, P: Read> TypedReceiver<T, E, P>
+{
+    /// Wraps a pipe (or any other `Read` type), with a maximum encoded size
+    /// of `DEFAULT_MAX_MESSAGE_SIZE`
+    pub fn new(inner: P) -> Self
+    {
+        Self::with_max_size(inner, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Wraps a pipe with the given maximum encoded size per value. Receiving
+    /// a larger value fails with `Error::MessageTooLarge`.
+    pub fn with_max_size(inner: P, max_size: usize) -> Self
+    {
+        TypedReceiver { inner: BufReader::new(inner), max_size, _marker: PhantomData }
+    }
+
+    /// Returns the maximum encoded size
+    pub fn max_size(&self) -> usize
+    {
+        self.max_size
+    }
+
+    /// Receives a value, blocking until one is available. Fails with an 
+    /// `UnexpectedEof` IO error if the pipe reaches EOF.
+    pub fn recv(&mut self) -> Result<T>
+    {
+        E::decode(&mut self.inner, self.max_size)?
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into())
+    }
+
+    /// Unwraps the underlying pipe. Any data which has been read from the pipe
+    /// but not yet decoded is lost.
+    pub fn into_inner(self) -> P
+    {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: DeserializeOwned, E: Encoding, P: Read> Iterator for TypedReceiver<T, E, P>
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        E::decode(&mut self.inner, self.max_size).transpose()
+    }
+}
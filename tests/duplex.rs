@@ -2,6 +2,7 @@ use ipipe::Pipe;
 use std::io::stdout;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::sync::{Arc, Barrier};
 use std::thread;
 
 use std::io::Write;
@@ -14,19 +15,24 @@ fn print_line(buf_reader: &mut BufReader<Pipe>)
     stdout().flush().unwrap();
 }
 
-fn client_server1(mut pipe: Pipe) 
+// A FIFO is half-duplex on Unix, so each side waits for the other to finish
+// its turn rather than reading back its own writes.
+fn client_server1(mut pipe: Pipe, turn: Arc<Barrier>) 
 {
     writeln!(pipe, "test1").unwrap();
     writeln!(pipe, "test2").unwrap();
     writeln!(pipe, "test3").unwrap();
+    turn.wait();
+    turn.wait();
     let mut buf_reader = BufReader::new(pipe);
     print_line(&mut buf_reader);
     print_line(&mut buf_reader);
     print_line(&mut buf_reader);
 }
 
-fn client_server2(pipe: Pipe) 
+fn client_server2(pipe: Pipe, turn: Arc<Barrier>) 
 {
+    turn.wait();
     let mut buf_reader = BufReader::new(pipe);
     print_line(&mut buf_reader);
     print_line(&mut buf_reader);
@@ -35,15 +41,19 @@ fn client_server2(pipe: Pipe)
     writeln!(pipe, "test4").unwrap();
     writeln!(pipe, "test5").unwrap();
     writeln!(pipe, "test6").unwrap();
+    turn.wait();
 }
 
 #[test]
 fn duplex_test() 
 {
     let pipe = Pipe::with_name("test2").unwrap();
-    let pipe_clone = pipe.clone();
-    let t1 = thread::spawn(|| client_server1(pipe));
-    let t2 = thread::spawn(|| client_server2(pipe_clone));
+    // Each side owns its handles, so neither is closed under the other
+    let pipe_clone = pipe.try_clone().unwrap();
+    let turn = Arc::new(Barrier::new(2));
+    let turn_clone = turn.clone();
+    let t1 = thread::spawn(move || client_server1(pipe, turn));
+    let t2 = thread::spawn(move || client_server2(pipe_clone, turn_clone));
     t1.join().unwrap();
     t2.join().unwrap();
 }